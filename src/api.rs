@@ -0,0 +1,128 @@
+use crate::config::init_simplicate_client;
+use crate::dryrun;
+use reqwest::{Client, Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Deserialize)]
+struct Response {
+    data: Option<Value>,
+    errors: Option<Value>,
+}
+
+pub enum Outcome {
+    /// The request was accepted by the API, carrying the `data` payload of the response
+    Accepted(Value),
+    /// The API refused the request, carrying the `errors` payload of the response
+    Rejected(Value),
+    /// The API could not be reached or failed on its end
+    Unreachable(String),
+}
+
+impl From<Result<reqwest::Response, reqwest::Error>> for Outcome {
+    fn from(result: Result<reqwest::Response, reqwest::Error>) -> Outcome {
+        match result {
+            Ok(mut resp) => {
+                if resp.status().is_server_error() {
+                    return Outcome::Unreachable(format!("API responded with {}", resp.status()));
+                }
+                let status = resp.status();
+                match resp.json::<Response>().ok() {
+                    Some(Response {
+                        errors: Some(err), ..
+                    }) => Outcome::Rejected(err),
+                    _ if !status.is_success() => {
+                        Outcome::Rejected(Value::String(format!("API responded with {}", status)))
+                    }
+                    Some(Response {
+                        data: Some(data), ..
+                    }) => Outcome::Accepted(data),
+                    _ => Outcome::Accepted(Value::Null),
+                }
+            }
+            Err(err) => Outcome::Unreachable(err.to_string()),
+        }
+    }
+}
+
+/// Id of the record created by a request, e.g. the hours booked by a POST
+pub fn created_id(data: &Value) -> Option<String> {
+    data.get("id").and_then(Value::as_str).map(String::from)
+}
+
+/// Request for the endpoints the simplicate client doesn't cover, addressed and authenticated
+/// with the same configuration as that client
+fn request(method: Method, path: &str) -> RequestBuilder {
+    let simplicate = init_simplicate_client();
+    Client::new()
+        .request(
+            method,
+            &format!("https://{}.simplicate.nl/api/v2/{}", simplicate.host, path),
+        )
+        .header("Authentication-Key", simplicate.api_key.as_str())
+        .header("Authentication-Secret", simplicate.api_secret.as_str())
+}
+
+#[derive(Deserialize)]
+struct Envelope<T> {
+    data: Option<T>,
+}
+
+/// Fetches a single record in the shape of `T`, failing with the reason it can't be retrieved
+pub fn fetch<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    fetch_with(path, &[])
+}
+
+/// Fetches the records matching the `query` parameters, e.g. `q[id][in]`, in the shape of `T`
+pub fn fetch_with<T: DeserializeOwned>(
+    path: &str,
+    query: &[(String, String)],
+) -> Result<T, String> {
+    let mut resp = request(Method::GET, path)
+        .query(query)
+        .send()
+        .map_err(|err| err.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("API responded with {}", resp.status()));
+    }
+    resp.json::<Envelope<T>>()
+        .map_err(|err| err.to_string())?
+        .data
+        .ok_or_else(|| format!("API returned no {}", path))
+}
+
+/// Fields of booked hours to change, unset fields are left untouched
+#[derive(Serialize, Default)]
+pub struct HoursUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hours: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<chrono::NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projectservice_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_id: Option<String>,
+}
+
+pub fn update_hours(id: &str, update: &HoursUpdate) -> Outcome {
+    let path = format!("hours/hours/{}", id);
+    if dryrun::enabled() {
+        dryrun::print_request("PUT", &path, Some(update));
+        return Outcome::Accepted(Value::Null);
+    }
+    request(Method::PUT, &path).json(update).send().into()
+}
+
+pub fn delete_hours(id: &str) -> Outcome {
+    let path = format!("hours/hours/{}", id);
+    if dryrun::enabled() {
+        dryrun::print_request::<Value>("DELETE", &path, None);
+        return Outcome::Accepted(Value::Null);
+    }
+    request(Method::DELETE, &path).send().into()
+}
@@ -0,0 +1,250 @@
+use crate::book::{book_hours, check_entries, format_note, Overrides};
+use crate::dates::{self, DateExpr};
+use crate::duration::Duration;
+use crate::error::{self, Error};
+use crate::links::Link;
+use crate::output;
+use crate::queue::{Queue, QueuedHours};
+use chrono::NaiveDateTime;
+use colored::*;
+use dialoguer::{theme::ColorfulTheme, Confirmation};
+use prettytable::Table;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+
+/// A row of a batch file as written by the user
+#[derive(Deserialize)]
+struct BatchRow {
+    alias: String,
+    duration: String,
+    date: Option<String>,
+    tickets: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BatchFile {
+    bookings: Vec<BatchRow>,
+}
+
+/// A validated row, ready to be booked
+struct BatchEntry {
+    link: Link,
+    hours: f64,
+    start_date: NaiveDateTime,
+    note: String,
+}
+
+fn read_source(path: &str) -> error::Result<String> {
+    let result = match path {
+        "-" => {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content).map(|_| content)
+        }
+        _ => fs::read_to_string(path),
+    };
+    result.map_err(|err| Error::Io(format!("Failed to read batch file: {}", err)))
+}
+
+/// Parses TOML files with `[[bookings]]` tables, falling back to CSV with a header row
+fn parse_rows(path: &str, content: &str) -> Result<Vec<BatchRow>, String> {
+    if path.ends_with(".toml") || (path == "-" && content.contains("[[bookings]]")) {
+        let file: BatchFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        return Ok(file.bookings);
+    }
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes())
+        .deserialize()
+        .collect::<Result<Vec<BatchRow>, csv::Error>>()
+        .map_err(|err| err.to_string())
+}
+
+fn validate(row: &BatchRow, links: &HashMap<String, Link>) -> Result<BatchEntry, String> {
+    let link = links
+        .get(&row.alias)
+        .ok_or_else(|| format!("no link for alias '{}'", row.alias))?;
+    let duration: Duration = row.duration.parse()?;
+    let start_date = match &row.date {
+        Some(date) if !date.is_empty() => date.parse::<DateExpr>()?.at_now(),
+        _ => dates::now(),
+    };
+    let tickets = row.tickets.as_ref().and_then(|tickets| {
+        let tickets: Vec<String> = tickets
+            .split(|c: char| c.is_whitespace() || c == '+')
+            .filter(|ticket| !ticket.is_empty())
+            .map(String::from)
+            .collect();
+        match tickets.is_empty() {
+            true => None,
+            false => Some(tickets),
+        }
+    });
+    Ok(BatchEntry {
+        link: link.clone(),
+        hours: duration.hours(),
+        start_date,
+        note: format_note(link, start_date, &tickets, &row.note).map_err(|err| err.to_string())?,
+    })
+}
+
+fn print_review(entries: &[BatchEntry]) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Index".bold().yellow(),
+        "Alias".bold().yellow(),
+        "Date".bold().yellow(),
+        "Hours".bold().yellow(),
+        "Note".bold().yellow()
+    ]);
+    for (index, entry) in entries.iter().enumerate() {
+        table.add_row(row![
+            index.to_string().bold(),
+            entry.link.alias.green().bold(),
+            entry.start_date.to_string().green(),
+            entry.hours.to_string().green(),
+            entry.note.green()
+        ]);
+    }
+    output::message(table.to_string().trim_end());
+    let total: f64 = entries.iter().map(|entry| entry.hours).sum();
+    output::message(format!(
+        "{} {}",
+        String::from("Total").bold().magenta(),
+        total.to_string().bold().green()
+    ));
+}
+
+/// Books every row of a CSV or TOML file, or of stdin when the path is `-`
+///
+/// All rows are validated and checked against the booked hours and limits before anything is
+/// sent, so a single mistake books nothing. Rows read from stdin are booked without asking for
+/// confirmation.
+pub fn book_file(path: &str, offline: bool, overrides: Overrides) -> error::Result<()> {
+    let content = read_source(path)?;
+    let rows = parse_rows(path, &content)
+        .map_err(|err| Error::Invalid(format!("Failed to read batch file: {}", err)))?;
+    let links = Link::get_mapping()?;
+    let mut entries = vec![];
+    let mut errors = vec![];
+    for (index, row) in rows.iter().enumerate() {
+        match validate(row, &links) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => errors.push(format!("row {}: {}", index + 1, err)),
+        }
+    }
+    if !errors.is_empty() {
+        output::message("No hours were booked, fix these rows first:".red());
+        for err in errors.iter() {
+            output::message(format!("  {}", err.yellow()));
+        }
+        return Err(Error::Invalid(format!(
+            "{} rows of the batch file are invalid",
+            errors.len()
+        )));
+    }
+    if entries.is_empty() {
+        output::message("The batch file has no bookings".yellow());
+        return Ok(());
+    }
+    print_review(&entries);
+    if !offline {
+        let checked: Vec<(&Link, f64, NaiveDateTime)> = entries
+            .iter()
+            .map(|entry| (&entry.link, entry.hours, entry.start_date))
+            .collect();
+        check_entries(&checked, overrides)?;
+    }
+    // stdin is taken by the batch itself, so there is no way to ask for confirmation
+    if path != "-" {
+        let confirmed = Confirmation::with_theme(&ColorfulTheme::default())
+            .with_text(&format!("Book these {} entries?", entries.len()))
+            .interact()?;
+        if !confirmed {
+            return Err(Error::Aborted);
+        }
+    }
+    let mut failed = 0;
+    for entry in entries {
+        let result = match offline {
+            true => Queue::push(QueuedHours::new(
+                &entry.link,
+                entry.hours,
+                entry.start_date,
+                entry.note,
+            )),
+            false => book_hours(
+                &entry.link,
+                entry.hours,
+                entry.start_date,
+                entry.note,
+                Overrides::checked(),
+            ),
+        };
+        if let Err(err) = result {
+            err.report();
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => {
+            output::message("All entries are processed".green().bold());
+            Ok(())
+        }
+        n => Err(Error::Incomplete(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(rows: Vec<BatchRow>) -> Vec<(String, String, Option<String>)> {
+        rows.into_iter()
+            .map(|row| (row.alias, row.duration, row.tickets))
+            .collect()
+    }
+
+    #[test]
+    fn parses_csv_rows() {
+        let content = "alias, duration, date, tickets, note\n\
+                       web, 1h30m, 2020-03-02, ABC-1 ABC-2, login\n\
+                       app, 45m, , , \n";
+        let rows = parse_rows("week.csv", content).unwrap();
+        assert_eq!(
+            summary(rows),
+            vec![
+                (
+                    String::from("web"),
+                    String::from("1h30m"),
+                    Some(String::from("ABC-1 ABC-2"))
+                ),
+                (String::from("app"), String::from("45m"), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_toml_bookings() {
+        let content = "[[bookings]]\nalias = \"web\"\nduration = \"2h\"\ntickets = \"ABC-1\"\n\n\
+                       [[bookings]]\nalias = \"app\"\nduration = \"1:15\"\n";
+        let expected = vec![
+            (
+                String::from("web"),
+                String::from("2h"),
+                Some(String::from("ABC-1")),
+            ),
+            (String::from("app"), String::from("1:15"), None),
+        ];
+        assert_eq!(summary(parse_rows("week.toml", content).unwrap()), expected);
+        assert_eq!(summary(parse_rows("-", content).unwrap()), expected);
+    }
+
+    #[test]
+    fn rejects_rows_without_a_duration() {
+        assert!(parse_rows("week.csv", "alias\nweb\n").is_err());
+        assert!(parse_rows("week.toml", "[[bookings]]\nalias = \"web\"\n").is_err());
+    }
+}
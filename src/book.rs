@@ -1,94 +1,810 @@
-use crate::config::{init_config_env, init_simplicate_client};
+use crate::api::{created_id, Outcome};
+use crate::batch::book_file;
+use crate::capacity::Capacity;
+use crate::config::{init_config_env, init_simplicate_client, UserConfig};
+use crate::dates::{self, DateExpr};
+use crate::dryrun;
+use crate::duration::{round, round_hours, Duration};
+use crate::error::{Error, Result};
+use crate::git::{branch_tickets, current_branch};
+use crate::history::History;
+use crate::hours::{booked_at, describe};
 use crate::links::Link;
-use chrono::offset::Utc;
-use chrono::NaiveDateTime;
+use crate::output;
+use crate::preflight::check_booking;
+use crate::queue::{Queue, QueuedHours};
+use crate::show::fetch_employee_hours;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use colored::*;
-use serde::Deserialize;
-use serde_json::{to_string_pretty, Value};
-use simplicate::structures::NewHours;
+use dialoguer::{theme::ColorfulTheme, Input};
+use serde::Serialize;
+use serde_json::Value;
+use simplicate::structures::{Hours, NewHours};
 use simplicate::Post;
 use std::env;
+use std::str::FromStr;
 use structopt::StructOpt;
 
+/// Alias given to book, paired with a time like `alias:1h30m` when splitting the hours
+#[derive(Debug, Clone)]
+pub struct AliasArg {
+    pub alias: String,
+    pub time: Option<Duration>,
+}
+
+impl FromStr for AliasArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<AliasArg, String> {
+        let mut parts = value.splitn(2, ':');
+        let alias = parts.next().unwrap_or("").to_string();
+        let time = match parts.next() {
+            Some(time) => Some(
+                time.parse()
+                    .map_err(|err| format!("'{}' has an invalid time: {}", value, err))?,
+            ),
+            None => None,
+        };
+        Ok(AliasArg { alias, time })
+    }
+}
+
+/// Time given to book, either the time spent or another `alias:time` pair of a split
+#[derive(Debug, Clone)]
+pub enum TimeArg {
+    Duration(Duration),
+    Split(AliasArg),
+}
+
+impl FromStr for TimeArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<TimeArg, String> {
+        match value.parse::<Duration>() {
+            Ok(duration) => Ok(TimeArg::Duration(duration)),
+            // durations like 1:30 contain a colon as well, so pairs are only tried after them
+            Err(_) if value.contains(':') => value.parse().map(TimeArg::Split),
+            Err(err) => Err(err),
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "book")]
 pub struct BookCommand {
-    /// Project alias
-    pub project_alias: String,
-    /// Hours spent
-    pub time: f64,
+    /// Project alias, or an `alias:time` pair to split the booking over several aliases; pick
+    /// one from a list when omitted
+    pub project_alias: Option<AliasArg>,
+    /// Time spent (e.g. 1h30m, 90m, 1:30, 1.5h or 45 in the configured default unit), or more
+    /// `alias:time` pairs; asked for when omitted
+    pub time: Vec<TimeArg>,
     #[structopt(short = "t")]
     /// Relevant tickets (e.g. LABD-001)
     pub tickets: Option<Vec<String>>,
     /// Additional context (e.g. 'took longer due to Amazon issues')
     #[structopt(short = "m")]
     pub context: Option<String>,
-    /// Specify a date for which you want to book (YYYY-MM-DDTHH-MM:SS)
-    #[structopt(short = "d")]
-    pub date: Option<NaiveDateTime>,
-}
-
-#[derive(Deserialize)]
-struct Response {
-    errors: Option<Value>,
+    /// Specify a date for which you want to book (YYYY-MM-DDTHH:MM:SS, YYYY-MM-DD, today,
+    /// yesterday, mon, last fri or -2d)
+    #[structopt(short = "d", allow_hyphen_values = true)]
+    pub date: Option<DateExpr>,
+    /// Start of the worked time range (HH:MM), books the hours until --to instead of a duration
+    #[structopt(
+        long = "from",
+        parse(try_from_str = dates::parse_time),
+        requires = "to",
+        conflicts_with = "time"
+    )]
+    pub from: Option<NaiveTime>,
+    /// End of the worked time range (HH:MM)
+    #[structopt(long = "to", parse(try_from_str = dates::parse_time), requires = "from")]
+    pub to: Option<NaiveTime>,
+    /// Queue the hours locally instead of booking them, use sync to book them later
+    #[structopt(long = "offline")]
+    pub offline: bool,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+    /// Don't take tickets from the git branch when -t is omitted
+    #[structopt(long = "no-git")]
+    pub no_git: bool,
+    /// Book every row of a CSV or TOML file (- for stdin) with alias, duration, date, tickets
+    /// and note
+    #[structopt(
+        long = "from-file",
+        conflicts_with_all = &["from", "project_alias", "time", "tickets", "context", "date"]
+    )]
+    pub from_file: Option<String>,
 }
 
 impl BookCommand {
-    pub fn execute(&self) {
-        init_config_env();
-        let link = Link::from_alias(self.project_alias.to_owned());
-        let newhours = NewHours {
-            hours: self.time,
-            project_id: link.project,
-            projectservice_id: link.service,
-            employee_id: env::var("SIMPL_EMPLOYEE_ID").expect("No employee ID is set"),
-            type_id: link.hourtype,
-            start_date: match self.date {
-                Some(dt) => dt,
-                None => Utc::now().naive_utc(),
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        if let Some(path) = &self.from_file {
+            return output::as_list(|| book_file(path, self.offline, self.overrides()));
+        }
+        let link = match &self.project_alias {
+            Some(arg) if arg.time.is_some() => return output::as_list(|| self.book_split()),
+            Some(arg) => match Link::get_mapping()?.get(&arg.alias) {
+                Some(link) => link.clone(),
+                None => {
+                    output::message(format!("{} '{}'", "No link for alias".yellow(), arg.alias));
+                    Link::from_picker(Some(arg.alias.to_owned()))?
+                }
             },
-            note: Some(self.format_note()),
+            None => Link::from_picker(None)?,
         };
-        let cli = init_simplicate_client();
-        let resp: Response = newhours
-            .post(cli)
-            .expect("Failed to book hours due to an error in the API")
-            .json()
-            .expect("Failed to parse response");
-        match resp.errors {
-            None => println!(
-                "{}\nHours: {}\nService: {}\nMessage: {}",
-                String::from("Booked hours succesfully!").green().bold(),
-                newhours.hours.to_string().yellow().italic(),
-                link.description.to_string().yellow().italic(),
-                newhours
-                    .note
-                    .unwrap_or("no message".to_string())
-                    .yellow()
-                    .italic()
-            ),
-            Some(err) => println!(
-                "{}\n\nError Response:\n{}\n\n{}\n\n",
-                "Failed to book hours due to a configuration error for the given alias, verify that the project is valid".red(),
-                format!("{}", to_string_pretty(&err).unwrap_or(String::from("No response"))).yellow().bold(),
-                "NOTE: If the error involves start/end dates of a project/service, this is caused by the configuration of the write_hours fields in simplicate.\nEnsure that either both fields for write_hours are filled, or both fields are empty.".italic().red()
-            ),
+        self.book_single(link)
+    }
+
+    /// Whether the missing time, tickets and note are asked for
+    fn is_interactive(&self) -> bool {
+        self.project_alias.is_none() || (self.time.is_empty() && self.from.is_none())
+    }
+
+    fn book_single(&self, link: Link) -> Result<()> {
+        // the time, tickets and note are asked for, which a script can't answer
+        if self.is_interactive() && !atty::is(atty::Stream::Stdin) {
+            return Err(Error::Invalid(String::from(
+                "No time given, and there is no terminal to ask for it",
+            )));
+        }
+        let (hours, start_date) = match self.time.is_empty() && self.from.is_none() {
+            true => {
+                let start_date = match self.date {
+                    Some(dt) => dt.at_now(),
+                    None => dates::now(),
+                };
+                (prompt_duration(None)?, start_date)
+            }
+            false => self.time_range()?,
         };
+        // check before asking for the note, so a refused booking doesn't waste the typing
+        if !self.offline {
+            check_hours(&[(&link, hours)], start_date, self.overrides())?;
+        }
+        let (tickets, context) = match self.is_interactive() {
+            true => self.prompt_note()?,
+            false => (self.tickets()?, self.context.to_owned()),
+        };
+        let note = format_note(&link, start_date, &tickets, &context)?;
+        match self.offline {
+            true => Queue::push(QueuedHours::new(&link, hours, start_date, note)),
+            false => {
+                book_hours(&link, hours, start_date, note, Overrides::checked())?;
+                print_remaining(start_date.date());
+                Ok(())
+            }
+        }
     }
 
-    fn format_note(&self) -> String {
+    /// Books every `alias:time` pair with the same date and note
+    ///
+    /// Every part is checked before the first is booked, so a refused part books nothing.
+    fn book_split(&self) -> Result<()> {
+        let splits = self.splits()?;
+        let start_date = match self.date {
+            Some(dt) => dt.at_now(),
+            None => dates::now(),
+        };
+        if !self.offline {
+            let parts: Vec<(&Link, f64)> =
+                splits.iter().map(|(link, hours)| (link, *hours)).collect();
+            check_hours(&parts, start_date, self.overrides())?;
+        }
+        let tickets = self.tickets()?;
+        let mut notes = vec![];
+        for (link, _) in splits.iter() {
+            notes.push(format_note(link, start_date, &tickets, &self.context)?);
+        }
+        let mut booked = 0.0;
+        let mut failed = 0;
+        for ((link, hours), note) in splits.into_iter().zip(notes) {
+            let result = match self.offline {
+                true => Queue::push(QueuedHours::new(&link, hours, start_date, note)),
+                false => book_hours(&link, hours, start_date, note, Overrides::checked()),
+            };
+            match result {
+                Ok(_) => booked += round_hours(hours)?,
+                Err(err) => {
+                    err.report();
+                    failed += 1;
+                }
+            }
+        }
+        output::message(format!(
+            "{} {} hours",
+            String::from("Total").bold().magenta(),
+            round(booked).to_string().bold().green()
+        ));
+        if !self.offline {
+            print_remaining(start_date.date());
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::Incomplete(n)),
+        }
+    }
+
+    /// Links and hours of the `alias:time` pairs, all validated before anything is booked
+    fn splits(&self) -> Result<Vec<(Link, f64)>> {
+        if self.from.is_some() {
+            return Err(Error::Invalid(String::from(
+                "--from and --to can't be combined with alias:time pairs",
+            )));
+        }
+        let links = Link::get_mapping()?;
+        let mut pairs: Vec<&AliasArg> = self.project_alias.iter().collect();
+        for time in self.time.iter() {
+            match time {
+                TimeArg::Split(pair) => pairs.push(pair),
+                TimeArg::Duration(_) => {
+                    return Err(Error::Invalid(String::from(
+                        "Give every part of a split as an alias:time pair",
+                    )))
+                }
+            }
+        }
+        let mut splits = vec![];
+        for pair in pairs {
+            let link = links
+                .get(&pair.alias)
+                .ok_or_else(|| Error::UnknownAlias(pair.alias.to_owned()))?;
+            let duration = pair.time.ok_or_else(|| {
+                Error::Invalid(format!("'{}' is not an alias:time pair", pair.alias))
+            })?;
+            splits.push((link.clone(), duration.hours()));
+        }
+        Ok(splits)
+    }
+
+    /// Checks of `book_hours` skipped with the flags given
+    fn overrides(&self) -> Overrides {
+        self.checks.overrides()
+    }
+
+    /// Asks for the tickets and context that were not given, offering the tickets of the git
+    /// branch as default
+    fn prompt_note(&self) -> Result<(Option<Vec<String>>, Option<String>)> {
         let tickets = match &self.tickets {
-            Some(tickets) => {
-                let inner = tickets.join("+");
-                format!("[{}] ", inner).to_string()
+            Some(tickets) => Some(tickets.to_owned()),
+            None => {
+                let branch = match self.no_git {
+                    true => vec![],
+                    false => branch_tickets()?,
+                };
+                let tickets: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Tickets (separated by spaces)")
+                    .default(branch.join(" "))
+                    .show_default(!branch.is_empty())
+                    .interact()?;
+                let tickets: Vec<String> = tickets.split_whitespace().map(String::from).collect();
+                match tickets.is_empty() {
+                    true => None,
+                    false => Some(tickets),
+                }
             }
-            None => String::from(""),
         };
-        let context = self
-            .context
-            .as_ref()
-            .unwrap_or(&String::from(""))
-            .to_owned();
-        format!("{}{}", tickets, context).to_string()
+        let context = match &self.context {
+            Some(context) => Some(context.to_owned()),
+            None => {
+                let context: String = Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Note")
+                    .default(String::new())
+                    .show_default(false)
+                    .interact()?;
+                match context.is_empty() {
+                    true => None,
+                    false => Some(context),
+                }
+            }
+        };
+        Ok((tickets, context))
+    }
+
+    /// Tickets given with -t, or else the ones in the name of the current git branch
+    fn tickets(&self) -> Result<Option<Vec<String>>> {
+        if self.tickets.is_some() || self.no_git {
+            return Ok(self.tickets.to_owned());
+        }
+        let tickets = branch_tickets()?;
+        match tickets.is_empty() {
+            true => Ok(None),
+            false => {
+                output::message(format!(
+                    "{} {}",
+                    "Using tickets from the git branch:".italic(),
+                    tickets.join(" ").yellow()
+                ));
+                Ok(Some(tickets))
+            }
+        }
+    }
+
+    /// Hours to book and their start date, either from --from and --to or from the duration
+    fn time_range(&self) -> Result<(f64, NaiveDateTime)> {
+        match (self.from, self.to) {
+            (Some(from), Some(to)) => {
+                let day = match self.date {
+                    Some(dt) => dt.date(),
+                    None => dates::today(),
+                };
+                let duration = Duration::from_hours((to - from).num_seconds() as f64 / 3600.0)
+                    .map_err(Error::Invalid)?;
+                Ok((duration.hours(), day.and_time(from)))
+            }
+            _ => {
+                let start_date = match self.date {
+                    Some(dt) => dt.at_now(),
+                    None => dates::now(),
+                };
+                let duration = match self.time.as_slice() {
+                    [] => return Err(Error::Invalid(String::from("No time spent given"))),
+                    [TimeArg::Duration(duration)] => *duration,
+                    _ => {
+                        return Err(Error::Invalid(String::from(
+                            "Give one time, or alias:time pairs to split",
+                        )))
+                    }
+                };
+                Ok((duration.hours(), start_date))
+            }
+        }
+    }
+}
+
+/// Prints the hours left of the day and its week, when limits are configured
+fn print_remaining(day: NaiveDate) {
+    if dryrun::enabled() {
+        return;
+    }
+    if let Ok(Some(capacity)) = Capacity::fetch(day) {
+        capacity.print_remaining();
+    }
+}
+
+/// Refuses hours that are already booked on the day
+fn check_duplicate(booked: &[Hours], link: &Link, hours: f64, day: NaiveDate) -> Result<()> {
+    let rounded = round_hours(hours)?;
+    let existing = booked.iter().find(|booked| {
+        booked_at(booked).map(|date| date.date()) == Some(day)
+            && is_same_booking(booked, link, rounded)
+    });
+    match existing {
+        Some(existing) => Err(Error::Refused(format!(
+            "these hours look like they are already booked, use --force to book them anyway\n{}",
+            describe(existing)
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Whether booked hours are on the same service and hour type as the link, for the same hours
+/// once these are `rounded` the way they would be booked
+pub fn is_same_booking(booked: &Hours, link: &Link, rounded: f64) -> bool {
+    let same_service = match &booked.projectservice {
+        Some(s) => s.id == link.service,
+        None => false,
+    };
+    let same_type = match &booked.hourtype {
+        Some(t) => t.id == link.hourtype,
+        None => false,
+    };
+    same_service && same_type && (booked.hours - rounded).abs() < 0.005
+}
+
+/// Removes the first booked hours that `matches` from `booked`, so every booking can account for
+/// only one of several identical ones
+pub fn take_booked<T, F: Fn(&T) -> bool>(booked: &mut Vec<T>, matches: F) -> bool {
+    match booked.iter().position(matches) {
+        Some(index) => {
+            booked.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Hours to send to the API, rounded with the rule from the config
+pub fn new_hours(
+    link: &Link,
+    hours: f64,
+    start_date: NaiveDateTime,
+    note: String,
+) -> Result<NewHours> {
+    Ok(NewHours {
+        hours: round_hours(hours)?,
+        project_id: link.project.to_owned(),
+        projectservice_id: link.service.to_owned(),
+        employee_id: env::var("SIMPL_EMPLOYEE_ID").map_err(|_| Error::ConfigMissing)?,
+        type_id: link.hourtype.to_owned(),
+        start_date,
+        note: Some(note),
+    })
+}
+
+pub fn post_hours(newhours: &NewHours) -> Outcome {
+    if dryrun::enabled() {
+        dryrun::print_request("POST", "hours/hours", Some(newhours));
+        return Outcome::Accepted(Value::Null);
+    }
+    let cli = init_simplicate_client();
+    newhours.post(cli).into()
+}
+
+/// Refuses hours that bring the day or week above the maximum from the config, counting the
+/// `pending` hours checked before them on other days of the week as booked
+fn check_limits(booked: &[Hours], day: NaiveDate, hours: f64, pending: f64) -> Result<()> {
+    let limits = match UserConfig::from_fs()? {
+        Some(cfg) if !cfg.limits.is_empty() => cfg.limits,
+        _ => return Ok(()),
+    };
+    let mut capacity = Capacity::new(limits, day, booked);
+    capacity.week_total += pending;
+    capacity.check(hours).map_err(|reason| {
+        Error::Refused(format!("{}, use --over-limit to book them anyway", reason))
+    })
+}
+
+/// Checks the parts of a booking before any is booked: refuses hours that are already booked
+/// on the day, rounded hours that together exceed the configured maximum of the day or week
+/// and hours the project or service doesn't accept
+///
+/// The booked hours are fetched once for all parts, and when that fails the booking goes ahead
+/// with a warning. Nothing is checked in a dry run.
+pub fn check_hours(
+    parts: &[(&Link, f64)],
+    start_date: NaiveDateTime,
+    overrides: Overrides,
+) -> Result<()> {
+    let entries: Vec<(&Link, f64, NaiveDateTime)> = parts
+        .iter()
+        .map(|(link, hours)| (*link, *hours, start_date))
+        .collect();
+    check_entries(&entries, overrides)
+}
+
+/// Checks bookings spread over several days like `check_hours`, fetching the booked hours once
+/// per week and checking the limits of each day with the entries of the days before it
+pub fn check_entries(entries: &[(&Link, f64, NaiveDateTime)], overrides: Overrides) -> Result<()> {
+    if dryrun::enabled() {
+        return Ok(());
+    }
+    let mut days: Vec<NaiveDate> = entries.iter().map(|(_, _, date)| date.date()).collect();
+    days.sort();
+    days.dedup();
+    if !overrides.duplicate || !overrides.over_limit {
+        let mut weeks: Vec<NaiveDate> = days.iter().map(|day| dates::week_start(*day)).collect();
+        weeks.dedup();
+        for start in weeks {
+            let end = start + chrono::Duration::days(6);
+            let booked = match fetch_employee_hours(start.and_hms(0, 0, 0), end.and_hms(23, 59, 59))
+            {
+                Ok(booked) => booked,
+                Err(Error::Unreachable(reason)) => {
+                    output::message(format!(
+                        "{} failed to fetch the hours of the week of {} ({}), booking \
+                             without checking for duplicates and limits",
+                        "Warning:".yellow().bold(),
+                        start,
+                        reason
+                    ));
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let mut pending = 0.0;
+            for day in days.iter().filter(|day| dates::week_start(**day) == start) {
+                let mut total = 0.0;
+                for (link, hours, _) in entries.iter().filter(|(_, _, date)| date.date() == *day) {
+                    if !overrides.duplicate {
+                        check_duplicate(&booked, link, *hours, *day)?;
+                    }
+                    total += round_hours(*hours)?;
+                }
+                if !overrides.over_limit {
+                    check_limits(&booked, *day, total, pending)?;
+                }
+                pending += total;
+            }
+        }
+    }
+    for (link, _, start_date) in entries {
+        check_booking(link, start_date.date()).map_err(Error::Refused)?;
+    }
+    Ok(())
+}
+
+/// Flags to skip the checks of `book_hours`, shared by every command that books hours
+#[derive(Debug, Default, StructOpt)]
+pub struct OverrideFlags {
+    /// Book even when the same hours are already booked on that day
+    #[structopt(long = "force")]
+    pub force: bool,
+    /// Book even when the hours exceed the maximum hours of the day or week
+    #[structopt(long = "over-limit")]
+    pub over_limit: bool,
+}
+
+impl OverrideFlags {
+    pub fn overrides(&self) -> Overrides {
+        Overrides {
+            duplicate: self.force,
+            over_limit: self.over_limit,
+        }
+    }
+}
+
+/// Checks of `book_hours` to skip, set with `OverrideFlags`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Overrides {
+    /// Book even when the same hours are already booked on that day
+    pub duplicate: bool,
+    /// Book even when the hours exceed the maximum hours of the day or week
+    pub over_limit: bool,
+}
+
+impl Overrides {
+    /// Skips the checks that were made already, e.g. for all parts of a split at once
+    pub fn checked() -> Overrides {
+        Overrides {
+            duplicate: true,
+            over_limit: true,
+        }
+    }
+}
+
+/// Books the hours for a link, queueing them for `simpl sync` when the API can't be reached
+///
+/// The hours are checked with `check_hours` first.
+pub fn book_hours(
+    link: &Link,
+    hours: f64,
+    start_date: NaiveDateTime,
+    note: String,
+    overrides: Overrides,
+) -> Result<()> {
+    check_hours(&[(link, hours)], start_date, overrides)?;
+    let newhours = new_hours(link, hours, start_date, note.to_owned())?;
+    let outcome = post_hours(&newhours);
+    History::record(link, &newhours, &outcome);
+    match outcome {
+        Outcome::Accepted(data) => {
+            print_booked(link, &newhours, hours, created_id(&data));
+            Ok(())
+        }
+        Outcome::Rejected(err) => Err(Error::Rejected(err)),
+        Outcome::Unreachable(reason) => {
+            output::message(format!(
+                "{}: {}",
+                "Failed to reach the API".red(),
+                reason.yellow()
+            ));
+            Queue::push(QueuedHours::new(link, hours, start_date, note))
+        }
+    }
+}
+
+/// Booked hours as printed with `--output json`
+#[derive(Serialize)]
+struct BookedHours<'a> {
+    /// Id of the hours in Simplicate, empty in a dry run
+    id: Option<String>,
+    alias: &'a str,
+    #[serde(flatten)]
+    hours: &'a NewHours,
+    /// Hours as given, before rounding
+    requested_hours: f64,
+}
+
+/// Prints booked hours, mentioning the `requested` hours when they were rounded
+pub fn print_booked(link: &Link, newhours: &NewHours, requested: f64, id: Option<String>) {
+    if output::json() {
+        return output::print_json(&BookedHours {
+            id,
+            alias: &link.alias,
+            hours: newhours,
+            requested_hours: requested,
+        });
+    }
+    let hours = match (newhours.hours - requested).abs() < 1e-9 {
+        true => newhours.hours.to_string(),
+        false => format!("{} (rounded from {})", newhours.hours, round(requested)),
+    };
+    println!(
+        "{}\nHours: {}\nService: {}\nMessage: {}",
+        match dryrun::enabled() {
+            true => String::from("Would book hours").cyan().bold(),
+            false => String::from("Booked hours succesfully!").green().bold(),
+        },
+        hours.yellow().italic(),
+        link.description.to_string().yellow().italic(),
+        newhours
+            .note
+            .to_owned()
+            .unwrap_or("no message".to_string())
+            .yellow()
+            .italic()
+    )
+}
+
+/// Asks for the time spent until it is a valid duration
+pub fn prompt_duration(default: Option<f64>) -> Result<f64> {
+    loop {
+        let theme = ColorfulTheme::default();
+        let mut input = Input::<String>::with_theme(&theme);
+        input.with_prompt("Time spent (e.g. 1h30m, 90m, 1:30 or 1.5h)");
+        if let Some(hours) = default {
+            input.default(hours.to_string());
+        }
+        let value = input.interact()?;
+        match value.parse::<Duration>() {
+            Ok(duration) => return Ok(duration.hours()),
+            Err(err) => output::message(format!("{}: {}", "Invalid duration".red(), err)),
+        }
+    }
+}
+
+/// Asks for the hours and note of a booking, offering the given ones as default
+pub fn prompt_booking(
+    link: &Link,
+    start_date: NaiveDateTime,
+    hours: f64,
+    note: String,
+) -> Result<(f64, String)> {
+    output::message(format!(
+        "{} {} {}",
+        link.alias.green().bold(),
+        "on".italic(),
+        start_date.date().to_string().green()
+    ));
+    let hours = prompt_duration(Some(hours))?;
+    let note: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Note")
+        .default(note)
+        .interact()?;
+    Ok((hours, note))
+}
+
+/// Writes the note for a booking with the template of the link or the config
+///
+/// Without a template the note reads `[T1+T2] context`, leaving out the brackets when there are
+/// no tickets.
+pub fn format_note(
+    link: &Link,
+    start_date: NaiveDateTime,
+    tickets: &Option<Vec<String>>,
+    context: &Option<String>,
+) -> Result<String> {
+    let config = UserConfig::from_fs()?
+        .map(|cfg| cfg.book)
+        .unwrap_or_default();
+    let separator = config.ticket_separator.unwrap_or("+".to_string());
+    let tickets = match tickets {
+        Some(tickets) => tickets.join(&separator),
+        None => String::from(""),
+    };
+    let context = context.as_ref().unwrap_or(&String::from("")).to_owned();
+    let template = match link.note_template.to_owned().or(config.note_template) {
+        Some(template) => template,
+        None => {
+            return Ok(match tickets.is_empty() {
+                true => context,
+                false => format!("[{}] {}", tickets, context),
+            })
+        }
+    };
+    let note = fill_template(&template, |placeholder| match placeholder {
+        "tickets" => Some(tickets.to_owned()),
+        "context" => Some(context.to_owned()),
+        "alias" => Some(link.alias.to_owned()),
+        "date" => Some(start_date.date().to_string()),
+        "branch" => Some(current_branch().unwrap_or_default()),
+        _ => None,
+    });
+    Ok(note.trim().to_string())
+}
+
+/// Replaces the `{name}` placeholders of a template in a single pass, so values are inserted
+/// literally even when they contain a placeholder themselves; unknown placeholders are kept
+fn fill_template<F: FnMut(&str) -> Option<String>>(template: &str, mut value: F) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let placeholder = &rest[open..];
+        let replaced = placeholder
+            .find('}')
+            .and_then(|close| value(&placeholder[1..close]).map(|value| (close, value)));
+        match replaced {
+            Some((close, value)) => {
+                filled.push_str(&value);
+                rest = &placeholder[close + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = &placeholder[1..];
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(template: &str) -> String {
+        fill_template(template, |placeholder| match placeholder {
+            "tickets" => Some(String::from("LABD-1+LABD-2")),
+            "context" => Some(String::from("fixed {alias} and {tickets}")),
+            "alias" => Some(String::from("labd")),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn fills_every_placeholder() {
+        assert_eq!(fill("[{tickets}] {alias}"), "[LABD-1+LABD-2] labd");
+        assert_eq!(fill("{alias}{alias}"), "labdlabd");
+        assert_eq!(fill("no placeholders"), "no placeholders");
+    }
+
+    #[test]
+    fn inserts_values_literally() {
+        assert_eq!(
+            fill("{alias}: {context}"),
+            "labd: fixed {alias} and {tickets}"
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_placeholders() {
+        assert_eq!(fill("{unknown} {alias}"), "{unknown} labd");
+        assert_eq!(fill("{{alias}}"), "{labd}");
+        assert_eq!(fill("{alias"), "{alias");
+        assert_eq!(fill("}{"), "}{");
+    }
+
+    #[test]
+    fn takes_each_booking_once() {
+        let mut booked = vec![1, 2, 2];
+        assert!(take_booked(&mut booked, |b| *b == 2));
+        assert!(take_booked(&mut booked, |b| *b == 2));
+        assert!(!take_booked(&mut booked, |b| *b == 2));
+        assert!(!take_booked(&mut booked, |b| *b == 3));
+        assert_eq!(booked, vec![1]);
+    }
+
+    fn split(value: &str) -> Option<(String, f64)> {
+        match value.parse::<TimeArg>() {
+            Ok(TimeArg::Split(AliasArg {
+                alias,
+                time: Some(time),
+            })) => Some((alias, time.hours())),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parses_times_and_pairs() {
+        match "1:30".parse::<TimeArg>() {
+            Ok(TimeArg::Duration(duration)) => assert_eq!(duration.hours(), 1.5),
+            other => panic!("1:30 parsed as {:?}", other),
+        }
+        assert_eq!(split("labd:1h30m"), Some((String::from("labd"), 1.5)));
+        assert_eq!(split("labd:1:30"), Some((String::from("labd"), 1.5)));
+        assert!("labd".parse::<TimeArg>().is_err());
+        assert!("labd:1x".parse::<TimeArg>().is_err());
+    }
+
+    #[test]
+    fn parses_aliases_with_and_without_time() {
+        let plain: AliasArg = "labd".parse().unwrap();
+        assert_eq!(plain.alias, "labd");
+        assert!(plain.time.is_none());
+        let pair: AliasArg = "labd:2h".parse().unwrap();
+        assert_eq!(pair.time.map(|time| time.hours()), Some(2.0));
+        assert!("labd:-1h".parse::<AliasArg>().is_err());
     }
 }
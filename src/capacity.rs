@@ -0,0 +1,149 @@
+use crate::config::{LimitsConfig, UserConfig};
+use crate::dates;
+use crate::duration::round;
+use crate::error::Result;
+use crate::hours::booked_at;
+use crate::output;
+use crate::show::fetch_employee_hours;
+use chrono::{Duration, NaiveDate};
+use colored::*;
+use simplicate::structures::Hours;
+
+/// Hours booked on a day and in its week, compared against the limits from the config
+pub struct Capacity {
+    limits: LimitsConfig,
+    pub day_total: f64,
+    pub week_total: f64,
+}
+
+impl Capacity {
+    /// Fetches the hours booked in the week of the day, `None` when no limits are configured
+    pub fn fetch(day: NaiveDate) -> Result<Option<Capacity>> {
+        let limits = match UserConfig::from_fs()? {
+            Some(cfg) if !cfg.limits.is_empty() => cfg.limits,
+            _ => return Ok(None),
+        };
+        let start = dates::week_start(day);
+        let end = start + Duration::days(6);
+        let booked = fetch_employee_hours(start.and_hms(0, 0, 0), end.and_hms(23, 59, 59))?;
+        Ok(Some(Capacity::new(limits, day, &booked)))
+    }
+
+    /// Totals of the hours booked in the week of the day
+    pub fn new(limits: LimitsConfig, day: NaiveDate, booked: &[Hours]) -> Capacity {
+        let mut capacity = Capacity {
+            limits,
+            day_total: 0.0,
+            week_total: 0.0,
+        };
+        for hours in booked {
+            if booked_at(hours).map(|date| date.date()) == Some(day) {
+                capacity.day_total += hours.hours;
+            }
+            capacity.week_total += hours.hours;
+        }
+        capacity
+    }
+
+    /// Refuses hours that exceed a maximum and warns about hours above the expected hours
+    ///
+    /// The hours are compared as given, so pass them rounded the way they will be booked.
+    pub fn check(&self, hours: f64) -> std::result::Result<(), String> {
+        let day = round(self.day_total + hours);
+        let week = round(self.week_total + hours);
+        if let Some(max) = self.limits.max_daily_hours {
+            if day > max {
+                return Err(format!(
+                    "this brings the day to {} hours, above the maximum of {}",
+                    day, max
+                ));
+            }
+        }
+        if let Some(max) = self.limits.max_weekly_hours {
+            if week > max {
+                return Err(format!(
+                    "this brings the week to {} hours, above the maximum of {}",
+                    week, max
+                ));
+            }
+        }
+        if let Some(expected) = self.limits.daily_hours {
+            if day > expected {
+                output::message(format!(
+                    "{} this brings the day to {} hours, more than the expected {}",
+                    "Warning:".yellow().bold(),
+                    day,
+                    expected
+                ));
+            }
+        }
+        if let Some(expected) = self.limits.weekly_hours {
+            if week > expected {
+                output::message(format!(
+                    "{} this brings the week to {} hours, more than the expected {}",
+                    "Warning:".yellow().bold(),
+                    week,
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hours left before the expected hours, or else the maximum, of the day and the week
+    pub fn remaining(&self) -> (Option<f64>, Option<f64>) {
+        let day = self.limits.daily_hours.or(self.limits.max_daily_hours);
+        let week = self.limits.weekly_hours.or(self.limits.max_weekly_hours);
+        (
+            day.map(|limit| round(limit - self.day_total)),
+            week.map(|limit| round(limit - self.week_total)),
+        )
+    }
+
+    pub fn print_remaining(&self) {
+        let (day, week) = self.remaining();
+        let mut parts = vec![];
+        if let Some(day) = day {
+            parts.push(format!("{} hours today", day.to_string().yellow().bold()));
+        }
+        if let Some(week) = week {
+            parts.push(format!(
+                "{} hours this week",
+                week.to_string().yellow().bold()
+            ));
+        }
+        output::message(format!("Remaining: {}", parts.join(", ")));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capacity(day_total: f64, week_total: f64) -> Capacity {
+        Capacity {
+            limits: LimitsConfig {
+                daily_hours: Some(8.0),
+                max_daily_hours: Some(10.0),
+                weekly_hours: None,
+                max_weekly_hours: Some(40.0),
+            },
+            day_total,
+            week_total,
+        }
+    }
+
+    #[test]
+    fn accepts_hours_up_to_the_maximum() {
+        assert!(capacity(6.0, 30.0).check(4.0).is_ok());
+        assert!(capacity(6.0, 30.0).check(8.0).is_err());
+        assert!(capacity(2.0, 38.0).check(2.5).is_err());
+        assert!(capacity(9.99, 30.0).check(0.01).is_ok());
+    }
+
+    #[test]
+    fn remains_below_the_expected_or_maximum_hours() {
+        assert_eq!(capacity(6.5, 30.0).remaining(), (Some(1.5), Some(10.0)));
+        assert_eq!(capacity(9.0, 42.0).remaining(), (Some(-1.0), Some(-2.0)));
+    }
+}
@@ -1,12 +1,20 @@
+use crate::dates;
+use crate::dryrun;
+use crate::duration::{Rounding, Unit};
+use crate::error::{Error, Result};
+use crate::output;
+use crate::recurring::Recurring;
 use colored::*;
 use dialoguer::{theme::ColorfulTheme, Input};
 use dirs::home_dir;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use simplicate::Client;
+use std::path::PathBuf;
 use std::{env, fs};
 use structopt::StructOpt;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct SimplicateConfig {
     pub api_key: String,
     pub api_secret: String,
@@ -14,6 +22,29 @@ pub struct SimplicateConfig {
     pub employee_id: String,
 }
 
+impl SimplicateConfig {
+    fn from_input() -> Result<SimplicateConfig> {
+        Ok(SimplicateConfig {
+            api_key: Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your API Key")
+                .default(env::var("SIMPL_API_KEY").unwrap_or(String::from("")))
+                .interact()?,
+            api_secret: Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your API Secret")
+                .default(env::var("SIMPL_API_SECRET").unwrap_or(String::from("")))
+                .interact()?,
+            employee_id: Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your employee id")
+                .default(env::var("SIMPL_EMPLOYEE_ID").unwrap_or(String::from("")))
+                .interact()?,
+            host: Input::with_theme(&ColorfulTheme::default())
+                .with_prompt("Enter your simpicate host")
+                .default(env::var("SIMPL_HOST").unwrap_or(String::from("")))
+                .interact()?,
+        })
+    }
+}
+
 pub fn init_simplicate_client() -> Client {
     Client {
         api_key: std::env::var("SIMPL_API_KEY").expect("No API key in configuration"),
@@ -22,58 +53,139 @@ pub fn init_simplicate_client() -> Client {
     }
 }
 
-pub fn init_config_env() {
-    match UserConfig::from_fs() {
-        Some(cfg) => cfg.set_env(),
-        None => panic!("No user configuration found. Please run the config command".yellow()),
-    }
+/// Path of a file in `~/.simpl`, where the configuration and the state of simpl are kept
+pub fn simpl_path(file: &str) -> Result<PathBuf> {
+    home_dir()
+        .map(|home| home.join(".simpl").join(file))
+        .ok_or_else(|| Error::Io(String::from("Can't find the home directory")))
+}
+
+/// Reads the timezone of the config for commands that work without one, failing when the
+/// config is invalid
+pub fn init_timezone() -> Result<()> {
+    UserConfig::from_fs().map(|_| ())
+}
+
+pub fn init_config_env() -> Result<()> {
+    UserConfig::load()?.set_env();
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Default)]
 pub struct UserConfig {
     pub simplicate: SimplicateConfig,
+    #[serde(default)]
+    pub book: BookConfig,
+    #[serde(default)]
+    pub dates: DatesConfig,
+    #[serde(default)]
+    pub git: GitConfig,
+    #[serde(default)]
+    pub recurring: Vec<Recurring>,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub fill: FillConfig,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct BookConfig {
+    /// Unit of durations given without one (e.g. `simpl book alias 45`)
+    #[serde(default)]
+    pub default_unit: Unit,
+    /// Template for notes with `{tickets}`, `{context}`, `{alias}`, `{date}` and `{branch}`
+    /// placeholders, `[{tickets}] {context}` when empty
+    pub note_template: Option<String>,
+    /// Text between tickets in the `{tickets}` placeholder, `+` when empty
+    pub ticket_separator: Option<String>,
+    /// Rounding applied to booked hours, e.g. `{ mode = "up", step = 0.25 }`
+    ///
+    /// Written as a table, so it has to come after the plain values above.
+    pub rounding: Option<Rounding>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct DatesConfig {
+    /// UTC offset (e.g. `+01:00`) in which dates are given, the system timezone when empty
+    pub timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct LimitsConfig {
+    /// Hours expected to be booked per day, booking more gives a warning
+    pub daily_hours: Option<f64>,
+    /// Hours that can't be exceeded on a day without --over-limit
+    pub max_daily_hours: Option<f64>,
+    /// Hours expected to be booked per week, booking more gives a warning
+    pub weekly_hours: Option<f64>,
+    /// Hours that can't be exceeded in a week without --over-limit
+    pub max_weekly_hours: Option<f64>,
+}
+
+impl LimitsConfig {
+    pub fn is_empty(&self) -> bool {
+        self.daily_hours.is_none()
+            && self.max_daily_hours.is_none()
+            && self.weekly_hours.is_none()
+            && self.max_weekly_hours.is_none()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct FillConfig {
+    /// Link alias that `simpl fill` books the remaining hours on when none is given
+    pub alias: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct GitConfig {
+    /// Regex matching ticket keys in branch names, `[A-Z][A-Z0-9]+-[0-9]+` when empty
+    pub ticket_pattern: Option<String>,
+    /// Repositories scanned by `simpl suggest`
+    #[serde(default)]
+    pub repositories: Vec<Repository>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Repository {
+    /// Path of the repository, may start with `~/`
+    pub path: String,
+    /// Link alias that commits in the repository are booked on
+    pub alias: String,
 }
 
 impl UserConfig {
-    pub fn from_fs() -> Option<UserConfig> {
-        let simplconf = home_dir()
-            .expect("Can't find homedir on this fs")
-            .join(".simpl/config.toml");
-        match fs::read_to_string(simplconf) {
-            Ok(string) => {
-                let config: UserConfig = toml::from_str(&string.to_string().to_owned()).unwrap();
-                Some(config)
-            }
-            Err(_) => None,
+    /// Reads and validates the configuration, and uses its timezone for the rest of the run
+    pub fn load() -> Result<UserConfig> {
+        let simplconf = simpl_path("config.toml")?;
+        let string = fs::read_to_string(simplconf).map_err(|_| Error::ConfigMissing)?;
+        let config: UserConfig =
+            toml::from_str(&string).map_err(|err| Error::ConfigInvalid(err.to_string()))?;
+        config.validate().map_err(Error::ConfigInvalid)?;
+        dates::set_timezone(&config.dates.timezone);
+        Ok(config)
+    }
+
+    /// The configuration for looking up optional settings, empty when there is none yet
+    pub fn from_fs() -> Result<Option<UserConfig>> {
+        match UserConfig::load() {
+            Ok(config) => Ok(Some(config)),
+            Err(Error::ConfigMissing) => Ok(None),
+            Err(err) => Err(err),
         }
     }
 
-    fn from_input() -> UserConfig {
-        let simplicate = SimplicateConfig {
-            api_key: Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Enter your API Key")
-                .default(env::var("SIMPL_API_KEY").unwrap_or(String::from("")))
-                .interact()
-                .unwrap(),
-            api_secret: Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Enter your API Secret")
-                .default(env::var("SIMPL_API_SECRET").unwrap_or(String::from("")))
-                .interact()
-                .unwrap(),
-            employee_id: Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Enter your employee id")
-                .default(env::var("SIMPL_EMPLOYEE_ID").unwrap_or(String::from("")))
-                .interact()
-                .unwrap(),
-            host: Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Enter your simpicate host")
-                .default(env::var("SIMPL_HOST").unwrap_or(String::from("")))
-                .interact()
-                .unwrap(),
-        };
-        UserConfig {
-            simplicate: simplicate,
+    fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(tz) = &self.dates.timezone {
+            dates::parse_offset(tz).ok_or_else(|| format!("'{}' is not a UTC offset", tz))?;
+        }
+        if let Some(rounding) = &self.book.rounding {
+            rounding.validate()?;
+        }
+        if let Some(pattern) = &self.git.ticket_pattern {
+            Regex::new(pattern).map_err(|err| format!("invalid ticket pattern: {}", err))?;
         }
+        Ok(())
     }
 
     fn set_env(&self) {
@@ -83,13 +195,32 @@ impl UserConfig {
         env::set_var("SIMPL_EMPLOYEE_ID", &self.simplicate.employee_id);
     }
 
-    fn store(&self) {
-        let homedir = dirs::home_dir().expect("Can't find homedir on this fs");
-        let simpldir = homedir.join(".simpl");
-        fs::create_dir_all(simpldir).expect("Failed to create simpl dir");
-        let simplconf = homedir.join(".simpl/config.toml");
-        let toml_string = toml::to_string(self).expect("Could not encode TOML value");
-        fs::write(simplconf, toml_string).expect("Failed to write config");
+    fn store(&self) -> Result<()> {
+        let simplconf = simpl_path("config.toml")?;
+        let toml_string = toml::to_string(self)
+            .map_err(|err| Error::Io(format!("Failed to encode config: {}", err)))?;
+        if dryrun::enabled() {
+            dryrun::print_diff(&simplconf, &toml_string);
+            return Ok(());
+        }
+        fs::create_dir_all(simpl_path("")?)
+            .and_then(|_| fs::write(simplconf, toml_string))
+            .map_err(|err| Error::Io(format!("Failed to write config: {}", err)))
+    }
+}
+
+impl UserConfig {
+    /// The configuration as printed with `--output json`, leaving out the API credentials
+    fn to_json(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).expect("Could not encode config");
+        if let Some(simplicate) = value
+            .get_mut("simplicate")
+            .and_then(serde_json::Value::as_object_mut)
+        {
+            simplicate.remove("api_key");
+            simplicate.remove("api_secret");
+        }
+        value
     }
 }
 
@@ -112,10 +243,33 @@ impl std::fmt::Display for UserConfig {
             "employee id".italic().green(),
             &self.simplicate.employee_id.blue()
         );
+        let line_6 = format!(
+            "{}: {}",
+            "default unit".italic().green(),
+            &self.book.default_unit.to_string().blue()
+        );
+        let line_7 = format!(
+            "{}: {}",
+            "timezone".italic().green(),
+            self.dates
+                .timezone
+                .as_ref()
+                .unwrap_or(&"system".to_string())
+                .blue()
+        );
+        let line_8 = format!(
+            "{}: {}",
+            "rounding".italic().green(),
+            match &self.book.rounding {
+                Some(rounding) => rounding.to_string(),
+                None => "none".to_string(),
+            }
+            .blue()
+        );
         write!(
             f,
-            "{}\n{}\n{}\n{}\n{}",
-            line_1, line_2, line_3, line_4, line_5
+            "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+            line_1, line_2, line_3, line_4, line_5, line_6, line_7, line_8
         )
     }
 }
@@ -125,14 +279,78 @@ impl std::fmt::Display for UserConfig {
 pub struct ConfigCommand {}
 
 impl ConfigCommand {
-    pub fn execute(&self) {
-        let current_config = UserConfig::from_fs();
-        match current_config {
-            Some(cfg) => cfg.set_env(),
-            None => println! {"No existing configuration found"},
+    pub fn execute(&self) -> Result<()> {
+        if output::json() {
+            output::print_json(&UserConfig::load()?.to_json());
+            return Ok(());
+        }
+        let mut new_config = match UserConfig::load() {
+            Ok(cfg) => {
+                cfg.set_env();
+                cfg
+            }
+            Err(Error::ConfigMissing) => {
+                println! {"No existing configuration found"};
+                UserConfig::default()
+            }
+            Err(err) => return Err(err),
         };
-        let new_config = UserConfig::from_input();
-        new_config.store();
+        new_config.simplicate = SimplicateConfig::from_input()?;
+        new_config.store()?;
         println!("The new configuration is: \n{}", new_config);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_every_section() {
+        let config = r#"
+            [simplicate]
+            api_key = "key"
+            api_secret = "secret"
+            host = "example"
+            employee_id = "employee:1"
+
+            [book]
+            default_unit = "minutes"
+            note_template = "{tickets}: {context}"
+            ticket_separator = ", "
+            rounding = { mode = "up", step = 0.25 }
+
+            [dates]
+            timezone = "+01:00"
+
+            [git]
+            ticket_pattern = "[A-Z]+-[0-9]+"
+
+            [[git.repositories]]
+            path = "~/src/web"
+            alias = "web"
+
+            [[recurring]]
+            alias = "standup"
+            duration = "15m"
+            days = "weekdays"
+
+            [limits]
+            daily_hours = 8.0
+            max_daily_hours = 10.0
+
+            [fill]
+            alias = "overhead"
+        "#;
+        let config: UserConfig = toml::from_str(config).unwrap();
+        let stored = toml::to_string(&config).unwrap();
+        let loaded: UserConfig = toml::from_str(&stored).unwrap();
+        assert_eq!(loaded.book.note_template, config.book.note_template);
+        assert_eq!(loaded.book.ticket_separator, config.book.ticket_separator);
+        assert_eq!(loaded.book.rounding.map(|r| r.step), Some(0.25));
+        assert_eq!(loaded.git.repositories.len(), 1);
+        assert_eq!(loaded.recurring.len(), 1);
+        assert_eq!(loaded.fill.alias, Some(String::from("overhead")));
     }
 }
@@ -0,0 +1,241 @@
+use crate::book::{
+    book_hours, is_same_booking, prompt_booking, take_booked, OverrideFlags, Overrides,
+};
+use crate::config::init_config_env;
+use crate::dates::{self, DateExpr};
+use crate::duration::round_hours;
+use crate::error::{Error, Result};
+use crate::hours::{booked_at, describe};
+use crate::links::Link;
+use crate::queue::{Queue, QueuedHours};
+use crate::show::fetch_employee_hours;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use colored::*;
+use dialoguer::{theme::ColorfulTheme, Checkboxes, Confirmation};
+use prettytable::Table;
+use structopt::StructOpt;
+
+/// Booked hours mapped to a link and moved to the target period
+struct CopiedHours {
+    link: Link,
+    hours: f64,
+    start_date: NaiveDateTime,
+    note: String,
+}
+
+fn print_copies(copies: &[CopiedHours]) {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Index".bold().yellow(),
+        "Alias".bold().yellow(),
+        "Date".bold().yellow(),
+        "Hours".bold().yellow(),
+        "Note".bold().yellow()
+    ]);
+    for (index, copy) in copies.iter().enumerate() {
+        table.add_row(row![
+            index.to_string().bold(),
+            copy.link.alias.green().bold(),
+            copy.start_date.to_string().green(),
+            copy.hours.to_string().green(),
+            copy.note.green()
+        ]);
+    }
+    table.printstd();
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "copy")]
+pub struct CopyCommand {
+    /// Day to copy the hours of (YYYY-MM-DD, today, yesterday, mon, last fri or -2d)
+    #[structopt(long = "from", allow_hyphen_values = true)]
+    pub from: DateExpr,
+    /// Day to book the hours on, today by default
+    #[structopt(long = "to", allow_hyphen_values = true)]
+    pub to: Option<DateExpr>,
+    /// Copy the whole week of --from to the week of --to, keeping the weekdays
+    #[structopt(long = "week")]
+    pub week: bool,
+    /// Queue the copied hours instead of booking them
+    #[structopt(long = "offline")]
+    pub offline: bool,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+}
+
+impl CopyCommand {
+    /// First and last day to copy from, and the number of days to move the hours by
+    fn periods(&self) -> Result<(NaiveDate, NaiveDate, Duration)> {
+        let source = self.from.date();
+        let target = match self.to {
+            Some(dt) => dt.date(),
+            None => dates::today(),
+        };
+        let (start, end, shift) = match self.week {
+            true => {
+                let start = dates::week_start(source);
+                (
+                    start,
+                    start + Duration::days(6),
+                    dates::week_start(target) - start,
+                )
+            }
+            false => (source, source, target - source),
+        };
+        match shift == Duration::zero() {
+            true => Err(Error::Invalid(format!(
+                "{} is the {} to copy from, pick another with --to",
+                target,
+                match self.week {
+                    true => "week",
+                    false => "day",
+                }
+            ))),
+            false => Ok((start, end, shift)),
+        }
+    }
+
+    /// Booked hours of the source period that map to a link, moved to the target period and
+    /// leaving out the ones the target period already has
+    fn copies(&self) -> Result<Vec<CopiedHours>> {
+        let (start, end, shift) = self.periods()?;
+        let booked = fetch_employee_hours(start.and_hms(0, 0, 0), end.and_hms(23, 59, 59))?;
+        let mut target = fetch_employee_hours(
+            (start + shift).and_hms(0, 0, 0),
+            (end + shift).and_hms(23, 59, 59),
+        )?;
+        let links = Link::get_links()?;
+        let mut copies = vec![];
+        for hours in booked {
+            let (link, start_date) = match (Link::from_hours(&links, &hours), booked_at(&hours)) {
+                (Some(link), Some(start_date)) => (link, start_date + shift),
+                _ => {
+                    println!("{} {}", "No link for".yellow(), describe(&hours));
+                    continue;
+                }
+            };
+            let rounded = round_hours(hours.hours)?;
+            let copied = take_booked(&mut target, |booked| {
+                booked_at(booked).map(|date| date.date()) == Some(start_date.date())
+                    && is_same_booking(booked, &link, rounded)
+            });
+            match copied {
+                true => println!("{} {}", "Already copied".yellow(), describe(&hours)),
+                false => copies.push(CopiedHours {
+                    link,
+                    hours: hours.hours,
+                    start_date,
+                    note: hours.note.to_owned().unwrap_or_default(),
+                }),
+            }
+        }
+        copies.sort_by_key(|copy| copy.start_date);
+        Ok(copies)
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let copies = self.copies()?;
+        if copies.is_empty() {
+            println!("{}", "No hours found to copy".yellow());
+            return Ok(());
+        }
+        print_copies(&copies);
+        let items: Vec<(String, bool)> = copies
+            .iter()
+            .map(|copy| {
+                let item = format!("{} {} {}", copy.link.alias, copy.start_date, copy.hours);
+                (item, true)
+            })
+            .collect();
+        let selected = Checkboxes::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select the hours to copy")
+            .items_checked(&items)
+            .interact()?;
+        if selected.is_empty() {
+            return Ok(());
+        }
+        let edit = Confirmation::with_theme(&ColorfulTheme::default())
+            .with_text("Change the hours or notes before booking?")
+            .default(false)
+            .interact()?;
+        let mut failed = 0;
+        for index in selected {
+            let copy = &copies[index];
+            let (hours, note) = match edit {
+                true => prompt_booking(
+                    &copy.link,
+                    copy.start_date,
+                    copy.hours,
+                    copy.note.to_owned(),
+                )?,
+                false => (copy.hours, copy.note.to_owned()),
+            };
+            let result = match self.offline {
+                true => Queue::push(QueuedHours::new(&copy.link, hours, copy.start_date, note)),
+                // the copies were matched against the target period above, unless edited since
+                false => book_hours(
+                    &copy.link,
+                    hours,
+                    copy.start_date,
+                    note,
+                    Overrides {
+                        duplicate: self.checks.force || !edit,
+                        ..self.checks.overrides()
+                    },
+                ),
+            };
+            if let Err(err) = result {
+                err.report();
+                failed += 1;
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::Incomplete(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn command(from: &str, to: &str, week: bool) -> CopyCommand {
+        CopyCommand {
+            from: DateExpr::Date(day(from)),
+            to: Some(DateExpr::Date(day(to))),
+            week,
+            offline: false,
+            checks: OverrideFlags::default(),
+        }
+    }
+
+    #[test]
+    fn moves_a_day_to_the_target() {
+        let (start, end, shift) = command("2019-11-11", "2019-11-13", false)
+            .periods()
+            .unwrap();
+        assert_eq!((start, end), (day("2019-11-11"), day("2019-11-11")));
+        assert_eq!(shift, Duration::days(2));
+    }
+
+    #[test]
+    fn moves_a_week_keeping_the_weekdays() {
+        let (start, end, shift) = command("2019-11-13", "2019-11-22", true).periods().unwrap();
+        assert_eq!((start, end), (day("2019-11-11"), day("2019-11-17")));
+        assert_eq!(shift, Duration::days(7));
+    }
+
+    #[test]
+    fn refuses_to_copy_onto_the_source() {
+        assert!(command("2019-11-11", "2019-11-11", false)
+            .periods()
+            .is_err());
+        assert!(command("2019-11-11", "2019-11-15", true).periods().is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use chrono::offset::{Local, Utc};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Weekday,
+};
+use std::cell::Cell;
+use std::str::FromStr;
+
+/// Parses a UTC offset like `+02:00`, `-0530`, `+1` or `UTC`
+pub fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("utc") || value == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match value.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    let digits: String = value[1..].chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+thread_local! {
+    /// Offset of the timezone from the config, set once per run when the config is loaded
+    static OFFSET: Cell<Option<FixedOffset>> = Cell::new(None);
+}
+
+/// Converts times with the timezone from the config for the rest of the run, or with the
+/// system timezone when it has none
+pub fn set_timezone(timezone: &Option<String>) {
+    OFFSET.with(|offset| offset.set(timezone.as_ref().and_then(|tz| parse_offset(tz))));
+}
+
+/// UTC offset configured as timezone, empty to use the system timezone
+fn configured_offset() -> Option<FixedOffset> {
+    OFFSET.with(Cell::get)
+}
+
+/// Wall-clock time of a moment in the configured timezone, which is what the API expects
+pub fn local(moment: DateTime<Utc>) -> NaiveDateTime {
+    match configured_offset() {
+        Some(offset) => moment.with_timezone(&offset).naive_local(),
+        None => moment.with_timezone(&Local).naive_local(),
+    }
+}
+
+/// Moment of a wall-clock time in the configured timezone, the inverse of `local`
+pub fn utc(time: NaiveDateTime) -> DateTime<Utc> {
+    let offset = match configured_offset() {
+        Some(offset) => offset,
+        None => match Local.from_local_datetime(&time).earliest() {
+            Some(moment) => *moment.offset(),
+            None => *Local.from_utc_datetime(&time).offset(),
+        },
+    };
+    DateTime::<Utc>::from_utc(
+        time - Duration::seconds(offset.local_minus_utc() as i64),
+        Utc,
+    )
+}
+
+pub fn now() -> NaiveDateTime {
+    local(Utc::now())
+}
+
+pub fn today() -> NaiveDate {
+    now().date()
+}
+
+/// Monday of the week the day falls in
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+pub fn parse_weekday(value: &str) -> Option<Weekday> {
+    match value {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Most recent date falling on the weekday, today included unless `strict` is set
+fn previous_weekday(from: NaiveDate, weekday: Weekday, strict: bool) -> NaiveDate {
+    let mut days_back = (7 + from.weekday().num_days_from_monday() as i64
+        - weekday.num_days_from_monday() as i64)
+        % 7;
+    if strict && days_back == 0 {
+        days_back = 7;
+    }
+    from - Duration::days(days_back)
+}
+
+/// Largest offset in days accepted by `parse_relative`, well within what chrono can represent
+const MAX_RELATIVE_DAYS: i64 = 1_000_000;
+
+/// Parses offsets from today like `-2d`, `+1d` or `-1w`
+fn parse_relative(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let rest = &value[1..];
+    let unit = rest.chars().last()?;
+    let digits = &rest[..rest.len() - unit.len_utf8()];
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let amount = digits.parse::<i64>().ok()?;
+    let days = match unit {
+        'd' => amount,
+        'w' => amount.checked_mul(7)?,
+        _ => return None,
+    };
+    if days > MAX_RELATIVE_DAYS {
+        return None;
+    }
+    today.checked_add_signed(Duration::days(sign * days))
+}
+
+/// A moment given on the command line, either a full date time or just a day
+///
+/// Accepts `2019-01-01T14:05:05`, `2019-01-01`, `today`, `yesterday`, weekday names (`mon`,
+/// `last fri`) and relative offsets (`-2d`, `-1w`), all in the configured timezone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateExpr {
+    DateTime(NaiveDateTime),
+    Date(NaiveDate),
+}
+
+impl DateExpr {
+    /// Resolves a day to the given time of day
+    pub fn at(&self, time: NaiveTime) -> NaiveDateTime {
+        match self {
+            DateExpr::DateTime(dt) => *dt,
+            DateExpr::Date(d) => d.and_time(time),
+        }
+    }
+
+    /// Resolves a day to the current time of day, used for bookings
+    pub fn at_now(&self) -> NaiveDateTime {
+        self.at(now().time())
+    }
+
+    /// Resolves a day to its first second
+    pub fn at_start(&self) -> NaiveDateTime {
+        match self {
+            DateExpr::DateTime(dt) => *dt,
+            DateExpr::Date(d) => d.and_hms(0, 0, 0),
+        }
+    }
+
+    /// Resolves a day to its last second
+    pub fn at_end(&self) -> NaiveDateTime {
+        match self {
+            DateExpr::DateTime(dt) => *dt,
+            DateExpr::Date(d) => d.and_hms(23, 59, 59),
+        }
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            DateExpr::DateTime(dt) => dt.date(),
+            DateExpr::Date(d) => *d,
+        }
+    }
+}
+
+impl DateExpr {
+    /// Parses an expression, resolving days like `yesterday` and `-2d` relative to `today`
+    fn parse(s: &str, today: NaiveDate) -> Result<DateExpr, String> {
+        let value = s.trim();
+        for format in &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M"] {
+            if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+                return Ok(DateExpr::DateTime(dt));
+            }
+        }
+        if let Ok(d) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(DateExpr::Date(d));
+        }
+        let value = value.to_lowercase();
+        let date = match value.as_str() {
+            "today" => Some(today),
+            "yesterday" => Some(today - Duration::days(1)),
+            "tomorrow" => Some(today + Duration::days(1)),
+            _ => match value.starts_with("last ") {
+                true => parse_weekday(value[5..].trim())
+                    .map(|weekday| previous_weekday(today, weekday, true)),
+                false => parse_weekday(&value)
+                    .map(|weekday| previous_weekday(today, weekday, false))
+                    .or_else(|| parse_relative(&value, today)),
+            },
+        };
+        match date {
+            Some(d) => Ok(DateExpr::Date(d)),
+            None => Err(format!(
+                "'{}' is not a date, use e.g. 2019-01-01T14:05:05, 2019-01-01, today, yesterday, mon, last fri or -2d",
+                s
+            )),
+        }
+    }
+}
+
+impl FromStr for DateExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DateExpr::parse(s, today())
+    }
+}
+
+/// Parses a time of day like `09:00` or `17:45:30`
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M"))
+        .map_err(|_| format!("'{}' is not a time of day, use e.g. 09:00", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd(y, m, d)
+    }
+
+    /// A wednesday
+    fn today() -> NaiveDate {
+        day(2019, 11, 13)
+    }
+
+    #[test]
+    fn parses_offsets() {
+        let cases = vec![
+            ("+02:00", Some(7200)),
+            ("-0530", Some(-19800)),
+            ("+1", Some(3600)),
+            ("UTC", Some(0)),
+            ("Z", Some(0)),
+            ("02:00", None),
+            ("+123", None),
+            ("+ab", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                parse_offset(value).map(|offset| offset.local_minus_utc()),
+                expected,
+                "parsing '{}'",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parses_relative_days() {
+        let cases = vec![
+            ("-2d", Some(day(2019, 11, 11))),
+            ("+1d", Some(day(2019, 11, 14))),
+            ("-1w", Some(day(2019, 11, 6))),
+            ("-0d", Some(today())),
+            ("-d", None),
+            ("-2x", None),
+            ("--2d", None),
+            ("-+2d", None),
+            ("2d", None),
+            ("-99999999999d", None),
+            ("-9999999999999999999w", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                parse_relative(value, today()),
+                expected,
+                "parsing '{}'",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parses_date_expressions() {
+        let cases = vec![
+            (
+                "2019-01-01T14:05:05",
+                DateExpr::DateTime(day(2019, 1, 1).and_hms(14, 5, 5)),
+            ),
+            (
+                "2019-01-01 14:05:05",
+                DateExpr::DateTime(day(2019, 1, 1).and_hms(14, 5, 5)),
+            ),
+            (
+                "2019-01-01T14:05",
+                DateExpr::DateTime(day(2019, 1, 1).and_hms(14, 5, 0)),
+            ),
+            ("2019-01-01", DateExpr::Date(day(2019, 1, 1))),
+            ("today", DateExpr::Date(today())),
+            ("Yesterday", DateExpr::Date(day(2019, 11, 12))),
+            ("tomorrow", DateExpr::Date(day(2019, 11, 14))),
+            ("wed", DateExpr::Date(today())),
+            ("last wed", DateExpr::Date(day(2019, 11, 6))),
+            ("mon", DateExpr::Date(day(2019, 11, 11))),
+            ("friday", DateExpr::Date(day(2019, 11, 8))),
+            ("-1w", DateExpr::Date(day(2019, 11, 6))),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                DateExpr::parse(value, today()),
+                Ok(expected),
+                "parsing '{}'",
+                value
+            );
+        }
+        for value in &["", "someday", "2019-13-01", "last", "-99999999999d"] {
+            assert!(
+                DateExpr::parse(value, today()).is_err(),
+                "'{}' parsed",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn finds_week_start() {
+        assert_eq!(week_start(today()), day(2019, 11, 11));
+        assert_eq!(week_start(day(2019, 11, 11)), day(2019, 11, 11));
+        assert_eq!(week_start(day(2019, 11, 17)), day(2019, 11, 11));
+    }
+
+    #[test]
+    fn converts_with_the_configured_timezone() {
+        set_timezone(&Some(String::from("+02:00")));
+        let moment = Utc.ymd(2019, 11, 11).and_hms(8, 0, 0);
+        assert_eq!(local(moment), day(2019, 11, 11).and_hms(10, 0, 0));
+        assert_eq!(utc(day(2019, 11, 11).and_hms(10, 0, 0)), moment);
+        set_timezone(&None);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::output;
+use colored::*;
+use serde::Serialize;
+use serde_json::to_string_pretty;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Whether the global `--dry-run` flag was given, or `SIMPL_DRY_RUN` is set to 1
+pub fn enabled() -> bool {
+    env::var("SIMPL_DRY_RUN")
+        .map(|value| value == "1")
+        .unwrap_or(false)
+}
+
+/// Prints a request that would have been sent to the API
+pub fn print_request<T: Serialize>(method: &str, path: &str, body: Option<&T>) {
+    output::message(format!(
+        "{} {} {}",
+        "[dry run] would send".cyan().bold(),
+        method.bold(),
+        path.italic()
+    ));
+    if let Some(body) = body {
+        output::message(
+            to_string_pretty(body)
+                .unwrap_or(String::from("Could not encode request"))
+                .yellow(),
+        );
+    }
+}
+
+/// Prints the lines that writing `new` to `path` would remove and add
+pub fn print_diff(path: &Path, new: &str) {
+    let old = fs::read_to_string(path).unwrap_or_default();
+    output::message(format!(
+        "{} {}",
+        "[dry run] would write".cyan().bold(),
+        path.display().to_string().italic()
+    ));
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let changes = diff(&old_lines, &new_lines);
+    for change in changes.iter() {
+        match change {
+            Change::Removed(line) => output::message(format!("- {}", mask(line)).red()),
+            Change::Added(line) => output::message(format!("+ {}", mask(line)).green()),
+        }
+    }
+    if changes.is_empty() {
+        output::message("no changes".italic());
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Change<'a> {
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Lines to remove from `old` and add to get `new`, keeping their longest common subsequence
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Change<'a>> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut changes = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(Change::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Hides the value of the API credentials in printed lines
+fn mask(line: &str) -> String {
+    match ["api_key", "api_secret"]
+        .iter()
+        .find(|key| line.trim_start().starts_with(*key))
+    {
+        Some(key) => format!("{} = \"hidden\"", key),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_repeated_lines() {
+        let old = vec![
+            "[[entries]]",
+            "hours = 1.0",
+            "",
+            "[entries.link]",
+            "alias = \"web\"",
+        ];
+        let mut new = old.clone();
+        new.extend(vec![
+            "",
+            "[[entries]]",
+            "hours = 2.0",
+            "",
+            "[entries.link]",
+            "alias = \"web\"",
+        ]);
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::Added(""),
+                Change::Added("[[entries]]"),
+                Change::Added("hours = 2.0"),
+                Change::Added(""),
+                Change::Added("[entries.link]"),
+                Change::Added("alias = \"web\""),
+            ]
+        );
+        assert_eq!(
+            diff(&["a", "b", "a"], &["a", "c", "a"]),
+            vec![Change::Removed("b"), Change::Added("c")]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn masks_the_api_credentials() {
+        assert_eq!(mask("api_key = \"abc\""), "api_key = \"hidden\"");
+        assert_eq!(mask("  api_secret = \"def\""), "api_secret = \"hidden\"");
+        assert_eq!(
+            mask("host = \"example.simplicate.nl\""),
+            "host = \"example.simplicate.nl\""
+        );
+    }
+}
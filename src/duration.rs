@@ -0,0 +1,312 @@
+use crate::config::UserConfig;
+use crate::error;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Longest duration that can be booked in a single entry
+const MAX_HOURS: f64 = 24.0;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Hours,
+    Minutes,
+}
+
+impl Default for Unit {
+    fn default() -> Unit {
+        Unit::Hours
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unit::Hours => write!(f, "hours"),
+            Unit::Minutes => write!(f, "minutes"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingMode {
+    Nearest,
+    Up,
+    Down,
+}
+
+impl std::fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoundingMode::Nearest => write!(f, "nearest"),
+            RoundingMode::Up => write!(f, "up"),
+            RoundingMode::Down => write!(f, "down"),
+        }
+    }
+}
+
+/// Granularity booked hours are rounded to, e.g. up to the next quarter of an hour
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Rounding {
+    pub mode: RoundingMode,
+    /// Step in hours, one of 0.1, 0.25 or 0.5
+    pub step: f64,
+}
+
+impl Rounding {
+    const STEPS: [f64; 3] = [0.1, 0.25, 0.5];
+
+    pub fn validate(&self) -> Result<(), String> {
+        match Self::STEPS
+            .iter()
+            .any(|step| (step - self.step).abs() < 1e-9)
+        {
+            true => Ok(()),
+            false => Err(format!(
+                "rounding step must be 0.1, 0.25 or 0.5 hours, not {}",
+                self.step
+            )),
+        }
+    }
+
+    /// Rounds hours to a multiple of the step, never rounding down to zero
+    pub fn apply(&self, hours: f64) -> f64 {
+        // Durations like 1:15 are not exact in floating point, so allow for a little slack
+        let steps = hours / self.step;
+        let steps = match self.mode {
+            RoundingMode::Nearest => steps.round(),
+            RoundingMode::Up => (steps - 1e-9).ceil(),
+            RoundingMode::Down => (steps + 1e-9).floor(),
+        };
+        round(steps.max(1.0) * self.step)
+    }
+}
+
+impl std::fmt::Display for Rounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            RoundingMode::Nearest => write!(f, "to the nearest {} hours", self.step),
+            mode => write!(f, "{} to {} hours", mode, self.step),
+        }
+    }
+}
+
+/// Rounds hours to two decimals, the precision `simpl show` displays them in
+pub fn round(hours: f64) -> f64 {
+    (hours * 100.0).round() / 100.0
+}
+
+/// Rounds hours with the rounding rule from the config, leaving them as is when there is none
+pub fn round_hours(hours: f64) -> error::Result<f64> {
+    let rounding = UserConfig::from_fs()?.and_then(|cfg| cfg.book.rounding);
+    Ok(match rounding {
+        Some(rounding) => rounding.apply(hours),
+        None => hours,
+    })
+}
+
+/// Time spent, parsed from `1h30m`, `90m`, `1:30`, `1.5h` or a bare number in the configured
+/// default unit
+#[derive(Debug, Clone, Copy)]
+pub struct Duration {
+    hours: f64,
+}
+
+impl Duration {
+    pub fn from_hours(hours: f64) -> Result<Duration, String> {
+        if hours.is_nan() || hours <= 0.0 {
+            return Err(String::from("Duration must be more than zero"));
+        }
+        if hours > MAX_HOURS {
+            return Err(format!(
+                "Duration of {} hours exceeds {} hours",
+                hours, MAX_HOURS
+            ));
+        }
+        Ok(Duration { hours })
+    }
+
+    pub fn hours(&self) -> f64 {
+        self.hours
+    }
+
+    /// Parses an unsigned number like `1`, `1.5` or `.5`
+    fn parse_number(value: &str) -> Result<f64, String> {
+        let digits = value.chars().filter(char::is_ascii_digit).count();
+        let points = value.chars().filter(|c| *c == '.').count();
+        if digits == 0 || points > 1 || digits + points != value.chars().count() {
+            return Err(format!("'{}' is not a valid number", value));
+        }
+        value
+            .parse::<f64>()
+            .map_err(|_| format!("'{}' is not a valid number", value))
+    }
+
+    fn parse_clock(value: &str) -> Result<f64, String> {
+        let mut parts = value.splitn(2, ':');
+        let hours = Self::parse_number(parts.next().unwrap_or(""))?;
+        let minutes = Self::parse_number(parts.next().unwrap_or(""))?;
+        if minutes >= 60.0 {
+            return Err(format!("'{}' has more than 59 minutes", value));
+        }
+        Ok(hours + minutes / 60.0)
+    }
+
+    fn parse_units(value: &str) -> Result<f64, String> {
+        let mut hours = 0.0;
+        let mut number = String::new();
+        let mut last_unit = None;
+        for c in value.chars() {
+            match c {
+                'h' | 'm' if last_unit == Some('m') || last_unit == Some(c) => {
+                    return Err(format!(
+                        "'{}' repeats a unit or has hours after minutes",
+                        value
+                    ));
+                }
+                'h' => {
+                    hours += Self::parse_number(&number)?;
+                    number.clear();
+                    last_unit = Some(c);
+                }
+                'm' => {
+                    hours += Self::parse_number(&number)? / 60.0;
+                    number.clear();
+                    last_unit = Some(c);
+                }
+                _ => number.push(c),
+            }
+        }
+        // Trailing digits after hours are minutes, e.g. `1h30`
+        if !number.is_empty() {
+            if last_unit != Some('h') {
+                return Err(format!("'{}' has a number without a unit", value));
+            }
+            hours += Self::parse_number(&number)? / 60.0;
+        }
+        Ok(hours)
+    }
+
+    /// Parses a duration, reading bare numbers in `default_unit`
+    fn parse(value: &str, default_unit: Unit) -> Result<Duration, String> {
+        let value = value.trim().to_lowercase();
+        let hours = if value.contains(':') {
+            Self::parse_clock(&value)?
+        } else if value.contains('h') || value.ends_with('m') {
+            Self::parse_units(&value)?
+        } else {
+            match default_unit {
+                Unit::Hours => Self::parse_number(&value)?,
+                Unit::Minutes => Self::parse_number(&value)? / 60.0,
+            }
+        };
+        Duration::from_hours(hours)
+    }
+}
+
+impl FromStr for Duration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let default_unit = match UserConfig::from_fs().map_err(|err| err.to_string())? {
+            Some(cfg) => cfg.book.default_unit,
+            None => Unit::default(),
+        };
+        Duration::parse(s, default_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours(value: &str, unit: Unit) -> Result<f64, String> {
+        Duration::parse(value, unit).map(|d| (d.hours() * 1000.0).round() / 1000.0)
+    }
+
+    #[test]
+    fn parses_valid_durations() {
+        let cases = vec![
+            ("1h30m", Unit::Hours, 1.5),
+            ("1h30", Unit::Hours, 1.5),
+            ("1h", Unit::Hours, 1.0),
+            ("90m", Unit::Hours, 1.5),
+            ("1:30", Unit::Hours, 1.5),
+            ("1:15", Unit::Hours, 1.25),
+            ("0:45", Unit::Hours, 0.75),
+            ("1.5h", Unit::Hours, 1.5),
+            (".5h", Unit::Hours, 0.5),
+            (" 2H ", Unit::Hours, 2.0),
+            ("1.5", Unit::Hours, 1.5),
+            ("45", Unit::Minutes, 0.75),
+            ("24h", Unit::Hours, 24.0),
+        ];
+        for (value, unit, expected) in cases {
+            assert_eq!(hours(value, unit), Ok(expected), "parsing '{}'", value);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        let cases = vec![
+            "", "abc", "h", "1x", "2h-30m", "1:-30", "-1:30", "-1", "+1", "1:60", "1:30:00", "0",
+            "0m", "25h", "1e2", "inf", "nan", "1..5", "30m1h", "1h2h", "1m30",
+        ];
+        for value in cases {
+            assert!(
+                hours(value, Unit::Hours).is_err(),
+                "'{}' should not parse",
+                value
+            );
+        }
+    }
+
+    fn rounding(mode: RoundingMode, step: f64) -> Rounding {
+        Rounding { mode, step }
+    }
+
+    #[test]
+    fn rounds_to_the_step() {
+        let cases = vec![
+            (RoundingMode::Nearest, 0.25, 1.1, 1.0),
+            (RoundingMode::Nearest, 0.25, 1.15, 1.25),
+            (RoundingMode::Up, 0.25, 1.01, 1.25),
+            (RoundingMode::Up, 0.25, 1.25, 1.25),
+            (RoundingMode::Down, 0.25, 1.24, 1.0),
+            (RoundingMode::Down, 0.5, 1.5, 1.5),
+            (RoundingMode::Up, 0.1, 1.0 + 1.0 / 60.0, 1.1),
+            (RoundingMode::Nearest, 0.1, 1.25, 1.3),
+        ];
+        for (mode, step, hours, expected) in cases {
+            assert_eq!(
+                rounding(mode, step).apply(hours),
+                expected,
+                "rounding {} {}",
+                hours,
+                rounding(mode, step)
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_exact_steps_despite_float_error() {
+        let quarter = Duration::parse("1:15", Unit::Hours).unwrap().hours();
+        assert_eq!(rounding(RoundingMode::Up, 0.25).apply(quarter), 1.25);
+        assert_eq!(rounding(RoundingMode::Down, 0.25).apply(quarter), 1.25);
+    }
+
+    #[test]
+    fn never_rounds_to_zero() {
+        assert_eq!(rounding(RoundingMode::Down, 0.5).apply(0.2), 0.5);
+        assert_eq!(rounding(RoundingMode::Nearest, 0.25).apply(0.05), 0.25);
+    }
+
+    #[test]
+    fn accepts_only_known_steps() {
+        assert!(rounding(RoundingMode::Up, 0.25).validate().is_ok());
+        assert!(rounding(RoundingMode::Up, 0.1).validate().is_ok());
+        assert!(rounding(RoundingMode::Up, 0.3).validate().is_err());
+        assert!(rounding(RoundingMode::Up, 0.0).validate().is_err());
+    }
+}
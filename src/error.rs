@@ -0,0 +1,88 @@
+use colored::*;
+use serde_json::{to_string_pretty, Value};
+use std::fmt;
+use std::io;
+
+/// Everything that can make a command fail, each with its own exit code
+#[derive(Debug)]
+pub enum Error {
+    /// There is no configuration yet
+    ConfigMissing,
+    /// A file in `~/.simpl` can't be parsed or holds an invalid setting
+    ConfigInvalid(String),
+    UnknownAlias(String),
+    /// Invalid input on the command line, in a batch file or in a prompt
+    Invalid(String),
+    /// A booking was refused before it was sent, e.g. for a closed project or a duplicate
+    Refused(String),
+    /// The API could not be reached or failed on its end
+    Unreachable(String),
+    /// The API refused the request, carrying the `errors` payload of the response
+    Rejected(Value),
+    /// Some of the entries of a command that books several could not be booked
+    Incomplete(usize),
+    /// The user declined to continue
+    Aborted,
+    /// A local file can't be read or written
+    Io(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Prints the error for the user, also used for the entries of commands that book several
+    pub fn report(&self) {
+        eprintln!("{} {}", "Error:".red().bold(), self);
+    }
+
+    /// Exit code of the process when a command fails with this error, as listed in the readme
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Invalid(_) => 1,
+            Error::ConfigMissing | Error::ConfigInvalid(_) => 2,
+            Error::UnknownAlias(_) => 3,
+            Error::Unreachable(_) => 4,
+            Error::Rejected(_) => 5,
+            Error::Refused(_) => 6,
+            Error::Incomplete(_) => 7,
+            Error::Aborted => 8,
+            Error::Io(_) => 9,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConfigMissing => write!(
+                f,
+                "No user configuration found. Please run the config command"
+            ),
+            Error::ConfigInvalid(reason) => write!(f, "Invalid configuration: {}", reason),
+            Error::UnknownAlias(alias) => write!(
+                f,
+                "No link for alias '{}', see simpl links show for the existing ones",
+                alias
+            ),
+            Error::Invalid(reason) => write!(f, "{}", reason),
+            Error::Refused(reason) => write!(f, "Can't book hours: {}", reason),
+            Error::Unreachable(reason) => write!(f, "Failed to reach the API: {}", reason),
+            Error::Rejected(err) => write!(
+                f,
+                "The API refused the request, verify that the project of the alias is valid\n\nError Response:\n{}",
+                to_string_pretty(err).unwrap_or(String::from("No response"))
+            ),
+            Error::Incomplete(failed) => {
+                write!(f, "{} entries failed, see the messages above", failed)
+            }
+            Error::Aborted => write!(f, "Aborted"),
+            Error::Io(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err.to_string())
+    }
+}
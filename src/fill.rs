@@ -0,0 +1,258 @@
+use crate::book::{book_hours, format_note, OverrideFlags};
+use crate::config::{init_config_env, UserConfig};
+use crate::dates::{self, DateExpr};
+use crate::duration::{round, Rounding};
+use crate::error::{Error, Result};
+use crate::hours::booked_at;
+use crate::links::Link;
+use crate::output;
+use crate::queue::Queue;
+use crate::show::fetch_employee_hours;
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use colored::*;
+use std::collections::HashMap;
+use structopt::StructOpt;
+
+/// Hours booked on a day and the end of the last of them
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Booked {
+    hours: f64,
+    end: Option<NaiveDateTime>,
+}
+
+impl Booked {
+    /// Start of the block filling the day, after the last booking or at 09:00 on an empty day
+    fn block_start(&self, day: NaiveDate) -> NaiveDateTime {
+        match self.end {
+            // a booking running past midnight still leaves the block on its own day
+            Some(end) => end.min(day.and_hms(23, 59, 0)),
+            None => day.and_time(NaiveTime::from_hms(9, 0, 0)),
+        }
+    }
+}
+
+/// Totals the bookings given by their start and hours per day
+fn tally<I: IntoIterator<Item = (NaiveDateTime, f64)>>(bookings: I) -> HashMap<NaiveDate, Booked> {
+    let mut totals: HashMap<NaiveDate, Booked> = HashMap::new();
+    for (start, hours) in bookings {
+        let booked = totals.entry(start.date()).or_default();
+        booked.hours += hours;
+        let end = start + Duration::seconds((hours * 3600.0).round() as i64);
+        booked.end = booked.end.max(Some(end));
+    }
+    totals
+}
+
+/// Hours booked and waiting in the queue on each day from `start` to `end`
+fn booked_per_day(start: NaiveDate, end: NaiveDate) -> Result<HashMap<NaiveDate, Booked>> {
+    let booked = fetch_employee_hours(start.and_hms(0, 0, 0), end.and_hms(23, 59, 59))?;
+    let booked = booked
+        .into_iter()
+        .filter_map(|hours| booked_at(&hours).map(|date| (date, hours.hours)));
+    let queued = Queue::load()?
+        .entries
+        .into_iter()
+        // rejected entries are never synced again, so they leave the day short
+        .filter(|entry| entry.rejected.is_none())
+        .map(|entry| (entry.start_date, entry.hours))
+        .filter(|(date, _)| date.date() >= start && date.date() <= end);
+    Ok(tally(booked.chain(queued)))
+}
+
+/// Hours left to reach the target, refused when there are none or rounding passes the target
+fn remaining(
+    target: f64,
+    booked: f64,
+    rounding: Option<Rounding>,
+) -> std::result::Result<f64, String> {
+    let booked = round(booked);
+    if booked >= target {
+        return Err(format!(
+            "already has {} hours booked, the target is {}",
+            booked, target
+        ));
+    }
+    let hours = round(target - booked);
+    if let Some(rounding) = rounding {
+        let rounded = rounding.apply(hours);
+        if rounded > hours + 1e-9 {
+            return Err(format!(
+                "has {} hours left, rounding them {} makes {} which passes the target of {}",
+                hours, rounding, rounded, target
+            ));
+        }
+    }
+    Ok(hours)
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "fill")]
+pub struct FillCommand {
+    /// Day to fill (YYYY-MM-DD, today, yesterday, mon, last fri or -2d), today by default
+    #[structopt(allow_hyphen_values = true)]
+    pub date: Option<String>,
+    /// Alias to book the remaining hours on, the fill alias from the config by default
+    pub alias: Option<String>,
+    /// Fill every workday of the week of the date, up to today
+    #[structopt(long = "week")]
+    pub week: bool,
+    /// Additional context for the note (e.g. 'internal meetings')
+    #[structopt(short = "m")]
+    pub context: Option<String>,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+}
+
+impl FillCommand {
+    /// The day and alias given, where a single argument that is no date is taken as the alias
+    fn arguments(&self) -> Result<(NaiveDate, Option<String>)> {
+        let date = match &self.date {
+            Some(date) => date,
+            None => return Ok((dates::today(), None)),
+        };
+        match (date.parse::<DateExpr>(), &self.alias) {
+            (Ok(dt), alias) => Ok((dt.date(), alias.to_owned())),
+            (Err(_), None) => Ok((dates::today(), Some(date.to_owned()))),
+            (Err(err), Some(_)) => Err(Error::Invalid(err)),
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let config = UserConfig::load()?;
+        let target = config.limits.daily_hours.ok_or_else(|| {
+            Error::ConfigInvalid(String::from(
+                "no daily target, set daily_hours in the [limits] section",
+            ))
+        })?;
+        let (day, alias) = self.arguments()?;
+        let alias = alias.or(config.fill.alias).ok_or_else(|| {
+            Error::Invalid(String::from(
+                "No alias given and no fill alias configured, set alias in the [fill] section",
+            ))
+        })?;
+        let link = Link::from_alias(alias)?;
+        let days: Vec<NaiveDate> = match self.week {
+            true => {
+                let start = dates::week_start(day);
+                (0..5).map(|n| start + Duration::days(n)).collect()
+            }
+            false => vec![day],
+        };
+        let rounding = config.book.rounding;
+        match self.week {
+            true => output::as_list(|| self.fill(&link, target, rounding, &days)),
+            false => self.fill(&link, target, rounding, &days),
+        }
+    }
+
+    /// Books the hours left to reach the `target` on each of the `days`
+    fn fill(
+        &self,
+        link: &Link,
+        target: f64,
+        rounding: Option<Rounding>,
+        days: &[NaiveDate],
+    ) -> Result<()> {
+        let totals = booked_per_day(days[0], days[days.len() - 1])?;
+        let mut filled = 0.0;
+        let mut failed = 0;
+        for &day in days {
+            if self.week && day > dates::today() {
+                break;
+            }
+            let booked = totals.get(&day).cloned().unwrap_or_default();
+            let hours = match remaining(target, booked.hours, rounding) {
+                Ok(hours) => hours,
+                Err(reason) => {
+                    let reason = format!("{} {}", day, reason);
+                    match self.week {
+                        true => {
+                            output::message(format!("{} {}", "Skipping".yellow(), reason));
+                            continue;
+                        }
+                        false => return Err(Error::Refused(reason)),
+                    }
+                }
+            };
+            let start_date = booked.block_start(day);
+            let note = format_note(link, start_date, &None, &self.context)?;
+            match book_hours(link, hours, start_date, note, self.checks.overrides()) {
+                Ok(_) => filled += hours,
+                Err(err) => {
+                    err.report();
+                    failed += 1;
+                }
+            }
+        }
+        if self.week {
+            output::message(format!(
+                "{} {} hours",
+                String::from("Filled").bold().magenta(),
+                round(filled).to_string().bold().green()
+            ));
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::Incomplete(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::duration::RoundingMode;
+
+    fn at(day: u32, hour: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(2020, 3, day).and_hms(hour, min, 0)
+    }
+
+    #[test]
+    fn tallies_hours_and_last_end_per_day() {
+        let totals = tally(vec![
+            (at(2, 13, 0), 2.5),
+            (at(2, 9, 0), 1.0),
+            (at(3, 10, 0), 0.25),
+        ]);
+        let day = NaiveDate::from_ymd(2020, 3, 2);
+        assert_eq!(
+            totals[&day],
+            Booked {
+                hours: 3.5,
+                end: Some(at(2, 15, 30)),
+            }
+        );
+        assert_eq!(totals[&day.succ()].end, Some(at(3, 10, 15)));
+    }
+
+    #[test]
+    fn starts_the_block_after_the_last_booking() {
+        let day = NaiveDate::from_ymd(2020, 3, 2);
+        assert_eq!(Booked::default().block_start(day), at(2, 9, 0));
+        let booked = tally(vec![(at(2, 10, 0), 2.0)])[&day];
+        assert_eq!(booked.block_start(day), at(2, 12, 0));
+        let late = tally(vec![(at(2, 22, 0), 4.0)])[&day];
+        assert_eq!(late.block_start(day), at(2, 23, 59));
+    }
+
+    #[test]
+    fn fills_up_to_the_target() {
+        let up = Rounding {
+            mode: RoundingMode::Up,
+            step: 0.25,
+        };
+        let down = Rounding {
+            mode: RoundingMode::Down,
+            step: 0.25,
+        };
+        assert_eq!(remaining(8.0, 5.5, None), Ok(2.5));
+        assert_eq!(remaining(8.0, 5.5, Some(up)), Ok(2.5));
+        assert_eq!(remaining(8.0, 7.9, Some(down)), Err(String::from(
+            "has 0.1 hours left, rounding them down to 0.25 hours makes 0.25 which passes the target of 8"
+        )));
+        assert!(remaining(8.0, 7.8, Some(up)).is_err());
+        assert!(remaining(8.0, 8.0, None).is_err());
+        assert!(remaining(8.0, 9.0, None).is_err());
+    }
+}
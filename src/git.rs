@@ -0,0 +1,129 @@
+use crate::config::{Repository, UserConfig};
+use crate::dates;
+use crate::error::{self, Error};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Ticket keys like `LAB-123`, used when the config has no pattern of its own
+const DEFAULT_TICKET_PATTERN: &str = "[A-Z][A-Z0-9]+-[0-9]+";
+
+/// A commit as read from `git log`
+pub struct Commit {
+    pub time: NaiveDateTime,
+    pub subject: String,
+}
+
+/// Runs git in the current directory, returning its trimmed output when it succeeds
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    match output.status.success() {
+        true => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => None,
+    }
+}
+
+/// Name of the branch checked out in the current directory, if it is a git repository
+pub fn current_branch() -> Option<String> {
+    match git(&["rev-parse", "--abbrev-ref", "HEAD"]) {
+        Some(branch) if !branch.is_empty() && branch != "HEAD" => Some(branch),
+        _ => None,
+    }
+}
+
+/// Pattern of ticket keys from the config
+pub fn ticket_pattern() -> error::Result<Regex> {
+    let pattern = UserConfig::from_fs()?
+        .and_then(|cfg| cfg.git.ticket_pattern)
+        .unwrap_or(DEFAULT_TICKET_PATTERN.to_string());
+    Regex::new(&pattern)
+        .map_err(|err| Error::ConfigInvalid(format!("invalid ticket pattern: {}", err)))
+}
+
+/// Distinct ticket keys in `text` matching `pattern`, in the order they appear
+pub fn find_tickets(pattern: &Regex, text: &str) -> Vec<String> {
+    let mut tickets: Vec<String> = vec![];
+    for found in pattern.find_iter(text) {
+        let ticket = found.as_str().to_string();
+        if !tickets.contains(&ticket) {
+            tickets.push(ticket);
+        }
+    }
+    tickets
+}
+
+/// Ticket keys in the name of the current branch
+pub fn branch_tickets() -> error::Result<Vec<String>> {
+    match current_branch() {
+        Some(branch) => Ok(find_tickets(&ticket_pattern()?, &branch)),
+        None => Ok(vec![]),
+    }
+}
+
+impl Repository {
+    pub fn dir(&self) -> Result<PathBuf, String> {
+        match self.path.starts_with("~/") {
+            true => dirs::home_dir()
+                .map(|home| home.join(&self.path[2..]))
+                .ok_or_else(|| format!("Can't find the home directory to resolve {}", self.path)),
+            false => Ok(PathBuf::from(&self.path)),
+        }
+    }
+
+    /// Commits on any branch by the user configured in the repository, between two moments
+    pub fn commits(&self, start: NaiveDateTime, end: NaiveDateTime) -> Result<Vec<Commit>, String> {
+        let dir = self.dir()?.to_string_lossy().to_string();
+        let author = git(&["-C", &dir, "config", "user.email"])
+            .ok_or_else(|| format!("{} is not a git repository with a user email", dir))?;
+        // git reads dates in its own timezone, so fetch a day extra on both ends and filter here
+        let since = format!("--since={}", (start.date().pred()).format("%Y-%m-%d"));
+        let until = format!("--until={}", (end.date().succ()).format("%Y-%m-%d"));
+        let log = git(&[
+            "-C",
+            &dir,
+            "log",
+            "--all",
+            "--no-merges",
+            // match the email as is, as git reads the author pattern as a regex
+            "--fixed-strings",
+            &format!("--author={}", author),
+            &since,
+            &until,
+            "--format=%ct %s",
+        ])
+        .ok_or_else(|| format!("Failed to read the git log of {}", dir))?;
+        let mut commits = vec![];
+        for line in log.lines() {
+            let mut parts = line.splitn(2, ' ');
+            let timestamp = match parts.next().and_then(|ts| ts.parse::<i64>().ok()) {
+                Some(timestamp) => timestamp,
+                None => continue,
+            };
+            let time = dates::local(Utc.timestamp(timestamp, 0));
+            if time >= start && time <= end {
+                commits.push(Commit {
+                    time,
+                    subject: parts.next().unwrap_or("").to_string(),
+                });
+            }
+        }
+        Ok(commits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_distinct_tickets_in_order() {
+        let regex = Regex::new(DEFAULT_TICKET_PATTERN).unwrap();
+        assert_eq!(
+            find_tickets(&regex, "feature/LAB-12-and-OPS2-3-LAB-12"),
+            vec!["LAB-12", "OPS2-3"]
+        );
+        assert!(find_tickets(&regex, "main").is_empty());
+        assert!(find_tickets(&regex, "fix/lab-12").is_empty());
+    }
+}
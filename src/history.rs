@@ -0,0 +1,372 @@
+use crate::api::{created_id, delete_hours, Outcome};
+use crate::config::{init_config_env, init_timezone, simpl_path};
+use crate::dates::{self, DateExpr};
+use crate::dryrun;
+use crate::error::{Error, Result};
+use crate::links::Link;
+use chrono::NaiveDateTime;
+use colored::*;
+use dialoguer::{theme::ColorfulTheme, Confirmation};
+use prettytable::Table;
+use serde::{Deserialize, Serialize};
+use serde_json::{to_string_pretty, Value};
+use simplicate::structures::NewHours;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Attempt {
+    Booked,
+    Rejected,
+    Unreachable,
+}
+
+/// Entries written before attempts were journaled only record bookings that succeeded
+impl Default for Attempt {
+    fn default() -> Attempt {
+        Attempt::Booked
+    }
+}
+
+impl std::fmt::Display for Attempt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Attempt::Booked => write!(f, "booked"),
+            Attempt::Rejected => write!(f, "rejected"),
+            Attempt::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    /// Moment the booking was attempted
+    #[serde(alias = "booked_at")]
+    pub timestamp: NaiveDateTime,
+    pub alias: String,
+    #[serde(default)]
+    pub outcome: Attempt,
+    /// Id of the booked hours in Simplicate
+    pub id: Option<String>,
+    pub hours: f64,
+    pub start_date: NaiveDateTime,
+    pub note: Option<String>,
+    /// Hours as they were sent to the API, empty for entries written before it was journaled
+    #[serde(default)]
+    pub payload: Value,
+    /// Error body of the API, or the reason it could not be reached
+    pub error: Option<Value>,
+    /// Set once the booking is removed again with undo
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl HistoryEntry {
+    fn matches(&self, text: &str) -> bool {
+        let text = text.to_lowercase();
+        let fields = [
+            self.alias.to_owned(),
+            self.note.to_owned().unwrap_or_default(),
+            self.id.to_owned().unwrap_or_default(),
+            self.payload.to_string(),
+            self.error
+                .as_ref()
+                .map(|err| err.to_string())
+                .unwrap_or_default(),
+        ];
+        fields
+            .iter()
+            .any(|field| field.to_lowercase().contains(&text))
+    }
+}
+
+/// Journal of booking attempts, stored as one JSON object per line
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    fn path() -> Result<PathBuf> {
+        simpl_path("history.jsonl")
+    }
+
+    pub fn load() -> Result<History> {
+        let entries = match fs::read_to_string(History::path()?) {
+            Ok(string) => string
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<serde_json::Result<_>>()
+                .map_err(|err| Error::ConfigInvalid(format!("history.jsonl: {}", err)))?,
+            Err(_) => vec![],
+        };
+        Ok(History { entries })
+    }
+
+    fn store(&self) -> Result<()> {
+        let lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).expect("Could not encode history entry"))
+            .collect();
+        let content = lines.join("\n") + "\n";
+        if dryrun::enabled() {
+            dryrun::print_diff(&History::path()?, &content);
+            return Ok(());
+        }
+        fs::write(History::path()?, content)
+            .map_err(|err| Error::Io(format!("Failed to write history: {}", err)))
+    }
+
+    fn append(entry: &HistoryEntry) -> Result<()> {
+        if dryrun::enabled() {
+            return Ok(());
+        }
+        let simpldir = simpl_path("")?;
+        let path = History::path()?;
+        let line = serde_json::to_string(entry).expect("Could not encode history entry");
+        fs::create_dir_all(&simpldir)
+            .and_then(|_| fs::OpenOptions::new().create(true).append(true).open(path))
+            .and_then(|mut file| writeln!(file, "{}", line))
+            .map_err(|err| Error::Io(format!("Failed to write history: {}", err)))
+    }
+
+    /// Journals an attempt to book hours together with the response of the API. A failure to
+    /// write the journal is reported but doesn't fail the booking, which already happened
+    pub fn record(link: &Link, newhours: &NewHours, outcome: &Outcome) {
+        let (attempt, id, error) = match outcome {
+            Outcome::Accepted(data) => (Attempt::Booked, created_id(data), None),
+            Outcome::Rejected(err) => (Attempt::Rejected, None, Some(err.to_owned())),
+            Outcome::Unreachable(reason) => (
+                Attempt::Unreachable,
+                None,
+                Some(Value::String(reason.to_owned())),
+            ),
+        };
+        let result = History::append(&HistoryEntry {
+            timestamp: dates::now(),
+            alias: link.alias.to_owned(),
+            outcome: attempt,
+            id,
+            hours: newhours.hours,
+            start_date: newhours.start_date,
+            note: newhours.note.to_owned(),
+            payload: serde_json::to_value(newhours).expect("Could not encode hours"),
+            error,
+            undone: false,
+        });
+        if let Err(err) = result {
+            err.report();
+        }
+    }
+
+    /// Marks the bookings of hours removed outside of undo, so undo doesn't remove them again
+    pub fn mark_removed(id: &str) -> Result<()> {
+        let mut history = History::load()?;
+        let mut changed = false;
+        for entry in history.entries.iter_mut() {
+            if entry.id.as_deref() == Some(id) && !entry.undone {
+                entry.undone = true;
+                changed = true;
+            }
+        }
+        match changed {
+            true => history.store(),
+            false => Ok(()),
+        }
+    }
+
+    /// Index of the most recent booking that can still be undone
+    fn last_booking(&self) -> Option<usize> {
+        self.entries.iter().rposition(|entry| {
+            entry.outcome == Attempt::Booked && entry.id.is_some() && !entry.undone
+        })
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "undo")]
+pub struct UndoCommand {}
+
+impl UndoCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let mut history = History::load()?;
+        let index = match history.last_booking() {
+            Some(index) => index,
+            None => {
+                println!("{}", "There are no bookings to undo".yellow());
+                return Ok(());
+            }
+        };
+        let entry = history.entries[index].clone();
+        let id = entry.id.to_owned().ok_or_else(|| {
+            Error::Invalid(String::from(
+                "The last booking in the history has no id, remove it with simpl hours rm",
+            ))
+        })?;
+        println!(
+            "{}\nAlias: {}\nHours: {}\nDate: {}\nMessage: {}",
+            "Last booking".bold().green(),
+            entry.alias.yellow().italic(),
+            entry.hours.to_string().yellow().italic(),
+            entry.start_date.to_string().yellow().italic(),
+            entry
+                .note
+                .to_owned()
+                .unwrap_or("no message".to_string())
+                .yellow()
+                .italic()
+        );
+        let confirmed = Confirmation::with_theme(&ColorfulTheme::default())
+            .with_text("Remove this booking?")
+            .interact()?;
+        if !confirmed {
+            return Err(Error::Aborted);
+        }
+        match delete_hours(&id) {
+            Outcome::Accepted(_) if dryrun::enabled() => Ok(()),
+            Outcome::Accepted(_) => {
+                history.entries[index].undone = true;
+                history.store()?;
+                println!("{}", "Removed booking succesfully!".green().bold());
+                Ok(())
+            }
+            Outcome::Rejected(err) => Err(Error::Rejected(err)),
+            Outcome::Unreachable(reason) => Err(Error::Unreachable(reason)),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "history")]
+pub struct HistoryCommand {
+    /// Only show bookings for this alias
+    #[structopt(short = "a")]
+    pub alias: Option<String>,
+
+    /// Only show bookings on or after this date (YYYY-MM-DD, today, yesterday, mon, last fri or
+    /// -2d)
+    #[structopt(short = "s", allow_hyphen_values = true)]
+    pub start_time: Option<DateExpr>,
+
+    /// Only show bookings on or before this date (YYYY-MM-DD, today, yesterday, mon, last fri or
+    /// -2d)
+    #[structopt(short = "e", allow_hyphen_values = true)]
+    pub end_time: Option<DateExpr>,
+
+    /// Only show bookings mentioning this text in their note, payload or error
+    #[structopt(short = "q")]
+    pub text: Option<String>,
+
+    /// Print the payload and error response of every booking
+    #[structopt(short = "v", long = "verbose")]
+    pub verbose: bool,
+}
+
+impl HistoryCommand {
+    fn includes(&self, entry: &HistoryEntry) -> bool {
+        let alias = match &self.alias {
+            Some(alias) => &entry.alias == alias,
+            None => true,
+        };
+        let start = match self.start_time {
+            Some(dt) => entry.start_date >= dt.at_start(),
+            None => true,
+        };
+        let end = match self.end_time {
+            Some(dt) => entry.start_date <= dt.at_end(),
+            None => true,
+        };
+        let text = match &self.text {
+            Some(text) => entry.matches(text),
+            None => true,
+        };
+        alias && start && end && text
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        init_timezone()?;
+        let entries: Vec<HistoryEntry> = History::load()?
+            .entries
+            .into_iter()
+            .filter(|entry| self.includes(entry))
+            .collect();
+        if entries.is_empty() {
+            println!("{}", "No bookings found".yellow());
+            return Ok(());
+        }
+        if self.verbose {
+            for entry in entries.iter() {
+                println!(
+                    "{} {} {}\n{}\n{}\n",
+                    entry.timestamp.to_string().magenta(),
+                    entry.alias.green().bold(),
+                    entry.outcome.to_string().bold(),
+                    to_string_pretty(&entry.payload)
+                        .unwrap_or_default()
+                        .yellow(),
+                    entry
+                        .error
+                        .as_ref()
+                        .map(|err| to_string_pretty(err).unwrap_or_default())
+                        .unwrap_or_default()
+                        .red()
+                );
+            }
+            return Ok(());
+        }
+        let mut table = Table::new();
+        table.add_row(row![
+            "Attempted".bold().yellow(),
+            "Alias".bold().yellow(),
+            "Date".bold().yellow(),
+            "Hours".bold().yellow(),
+            "Outcome".bold().yellow(),
+            "Note".bold().yellow()
+        ]);
+        for entry in entries.iter() {
+            let outcome = match (entry.outcome, entry.undone) {
+                (Attempt::Booked, true) => "undone".yellow(),
+                (Attempt::Booked, false) => "booked".green(),
+                (attempt, _) => attempt.to_string().red(),
+            };
+            table.add_row(row![
+                entry.timestamp.to_string().magenta(),
+                entry.alias.green().bold(),
+                entry.start_date.to_string(),
+                entry.hours.to_string(),
+                outcome,
+                entry.note.to_owned().unwrap_or_default()
+            ]);
+        }
+        table.printstd();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_entries_written_before_attempts_were_journaled() {
+        let line = r#"{"id":"hours:1","alias":"a","hours":1.5,"start_date":"2019-11-11T09:00:00","note":"x","booked_at":"2019-11-11T17:00:00"}"#;
+        let entry: HistoryEntry = serde_json::from_str(line).unwrap();
+        assert_eq!(entry.outcome, Attempt::Booked);
+        assert_eq!(entry.timestamp.to_string(), "2019-11-11 17:00:00");
+        assert_eq!(entry.payload, Value::Null);
+        assert!(!entry.undone);
+    }
+
+    #[test]
+    fn reads_current_entries() {
+        let line = r#"{"timestamp":"2019-11-11T17:00:00","alias":"a","outcome":"rejected","id":null,"hours":1.5,"start_date":"2019-11-11T09:00:00","note":null,"payload":{"hours":1.5},"error":"closed"}"#;
+        let entry: HistoryEntry = serde_json::from_str(line).unwrap();
+        assert_eq!(entry.outcome, Attempt::Rejected);
+        assert_eq!(entry.error, Some(Value::String(String::from("closed"))));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::api::{delete_hours, update_hours, HoursUpdate, Outcome};
+use crate::config::{init_config_env, init_simplicate_client};
+use crate::dates::DateExpr;
+use crate::dryrun;
+use crate::duration::Duration;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::links::Link;
+use chrono::NaiveDateTime;
+use colored::*;
+use dialoguer::{theme::ColorfulTheme, Confirmation};
+use simplicate::structures::Hours;
+use simplicate::QueryMany;
+use structopt::StructOpt;
+
+/// Format of the start dates returned by the API
+pub const API_DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Moment booked hours start at, `None` when the API gave no valid start date
+pub fn booked_at(hours: &Hours) -> Option<NaiveDateTime> {
+    hours
+        .start_date
+        .as_ref()
+        .and_then(|date| NaiveDateTime::parse_from_str(date, API_DATETIME_FORMAT).ok())
+}
+
+/// Booked hours by id, `None` when the API knows no such hours
+pub fn fetch_hours(id: &str) -> Result<Option<Hours>> {
+    let params = vec![(String::from("q[id]"), id.to_string())];
+    Hours::fetch_many(init_simplicate_client(), Some(params))
+        .map(|h| h.into_iter().next())
+        .ok_or_else(|| Error::Unreachable(String::from("Failed to fetch hours")))
+}
+
+pub fn describe(hours: &Hours) -> String {
+    let proj_name = match &hours.project {
+        Some(p) => p.name.to_owned().unwrap_or("Unnamed project".to_string()),
+        None => "Unnamed project".to_string(),
+    };
+    let serv_name = match &hours.projectservice {
+        Some(s) => s.name.to_owned().unwrap_or("Unnamed Service".to_string()),
+        None => "Unnamed Service".to_string(),
+    };
+    format!(
+        "{} {} hours on {} / {}: {}",
+        hours
+            .start_date
+            .to_owned()
+            .unwrap_or("unknown".to_string())
+            .magenta(),
+        hours.hours.to_string().green(),
+        proj_name.bright_red(),
+        serv_name.red(),
+        hours.note.to_owned().unwrap_or_default().yellow()
+    )
+}
+
+/// Booked hours by id, failing when the API can't be reached or knows no such hours
+fn fetch_existing(id: &str) -> Result<Hours> {
+    fetch_hours(id)?.ok_or_else(|| Error::Invalid(format!("No hours found for id '{}'", id)))
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "hours")]
+pub enum HoursCommand {
+    /// Change booked hours by id (see show --ids)
+    #[structopt(name = "edit")]
+    Edit {
+        id: String,
+        /// New time spent (e.g. 1h30m, 90m, 1:30, 1.5h or 45 in the configured default unit)
+        #[structopt(long = "hours")]
+        hours: Option<Duration>,
+        /// New note, replacing the current one
+        #[structopt(short = "m")]
+        note: Option<String>,
+        /// New date (YYYY-MM-DDTHH:MM:SS, or a day like 2019-01-01, yesterday or mon to keep
+        /// the time of day)
+        #[structopt(short = "d", allow_hyphen_values = true)]
+        date: Option<DateExpr>,
+        /// Move the hours to the service of another link alias
+        #[structopt(short = "a", long = "alias")]
+        alias: Option<String>,
+    },
+    /// Remove booked hours by id (see show --ids)
+    #[structopt(name = "rm")]
+    Remove { id: String },
+}
+
+impl HoursCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        match self {
+            HoursCommand::Edit {
+                id,
+                hours,
+                note,
+                date,
+                alias,
+            } => {
+                let current = fetch_existing(id)?;
+                let link = alias.to_owned().map(Link::from_alias).transpose()?;
+                let start_date = match date {
+                    Some(dt) => {
+                        let current_start = booked_at(&current).ok_or_else(|| {
+                            Error::Invalid(format!("Hours '{}' have no valid start date", id))
+                        })?;
+                        Some(dt.at(current_start.time()))
+                    }
+                    None => None,
+                };
+                let update = HoursUpdate {
+                    hours: hours.map(|h| h.hours()),
+                    note: note.to_owned(),
+                    start_date,
+                    project_id: link.as_ref().map(|l| l.project.to_owned()),
+                    projectservice_id: link.as_ref().map(|l| l.service.to_owned()),
+                    type_id: link.as_ref().map(|l| l.hourtype.to_owned()),
+                };
+                match update_hours(id, &update) {
+                    Outcome::Accepted(_) if dryrun::enabled() => Ok(()),
+                    Outcome::Accepted(_) => {
+                        println!("{}", "Updated hours succesfully!".green().bold());
+                        println!("{} {}", "Before:".bold(), describe(&current));
+                        if let Ok(Some(updated)) = fetch_hours(id) {
+                            println!("{} {}", "After: ".bold(), describe(&updated));
+                        }
+                        Ok(())
+                    }
+                    Outcome::Rejected(err) => Err(Error::Rejected(err)),
+                    Outcome::Unreachable(reason) => Err(Error::Unreachable(reason)),
+                }
+            }
+            HoursCommand::Remove { id } => {
+                let current = fetch_existing(id)?;
+                println!("{}", describe(&current));
+                let confirmed = Confirmation::with_theme(&ColorfulTheme::default())
+                    .with_text("Remove these hours?")
+                    .interact()?;
+                if !confirmed {
+                    return Err(Error::Aborted);
+                }
+                match delete_hours(id) {
+                    Outcome::Accepted(_) if dryrun::enabled() => Ok(()),
+                    Outcome::Accepted(_) => {
+                        println!("{}", "Removed hours succesfully!".green().bold());
+                        History::mark_removed(id)
+                    }
+                    Outcome::Rejected(err) => Err(Error::Rejected(err)),
+                    Outcome::Unreachable(reason) => Err(Error::Unreachable(reason)),
+                }
+            }
+        }
+    }
+}
@@ -1,11 +1,15 @@
-use crate::config::{init_config_env, init_simplicate_client};
+use crate::config::{init_config_env, init_simplicate_client, simpl_path};
+use crate::dryrun;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::output;
 use colored::*;
-use dialoguer::{theme::ColorfulTheme, Input};
+use dialoguer::{theme::ColorfulTheme, Input, Select};
 use prettytable::{Row, Table};
 use serde::{Deserialize, Serialize};
-use simplicate::structures::{HourType, Project, Service};
+use simplicate::structures::{HourType, Hours, Project, Service};
 use simplicate::QueryMany;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use structopt::StructOpt;
 use toml;
@@ -32,7 +36,7 @@ trait Promptable<T: QueryMany<T>> {
         items
     }
 
-    fn retrieve(offset: Option<u32>) -> Vec<T> {
+    fn retrieve(offset: Option<u32>) -> Result<Vec<T>> {
         let offset = match offset {
             Some(x) => x,
             None => 0,
@@ -52,14 +56,16 @@ trait Promptable<T: QueryMany<T>> {
             Some(mut list) => {
                 if list.len() == 100 {
                     let new_offset = offset + 100;
-                    let next = Self::retrieve(Some(new_offset));
+                    let next = Self::retrieve(Some(new_offset))?;
                     list.extend(next);
-                    list
+                    Ok(list)
                 } else {
-                    list
+                    Ok(list)
                 }
             }
-            None => panic!("fail"),
+            None => Err(Error::Unreachable(String::from(
+                "Failed to retrieve the options to pick from",
+            ))),
         }
     }
 
@@ -72,8 +78,8 @@ trait Promptable<T: QueryMany<T>> {
         table.printstd();
     }
 
-    fn prompt(filter: Option<Filter>) -> T {
-        let items = Self::retrieve(None);
+    fn prompt(filter: Option<Filter>) -> Result<T> {
+        let items = Self::retrieve(None)?;
         let items = Self::sort(items);
         let items = match filter {
             Some(f) => Self::filter(items, f),
@@ -82,9 +88,11 @@ trait Promptable<T: QueryMany<T>> {
         Self::print_options(&items);
         let selection: usize = Input::with_theme(&ColorfulTheme::default())
             .with_prompt(Self::PROMPT_TEXT)
-            .interact()
-            .unwrap();
-        items.into_iter().nth(selection).expect("Invalid Selection")
+            .interact()?;
+        items
+            .into_iter()
+            .nth(selection)
+            .ok_or_else(|| Error::Invalid(format!("{} is not one of the options", selection)))
     }
 }
 
@@ -99,8 +107,13 @@ impl Promptable<Project> for Link {
                     None => "Unknown".to_string(),
                 },
                 match &x.organization {
-                    Some(org) => org.name.as_ref().unwrap_or(&"Unknown".to_string()).to_string().to_uppercase(),
-                    None => "".to_string()
+                    Some(org) => org
+                        .name
+                        .as_ref()
+                        .unwrap_or(&"Unknown".to_string())
+                        .to_string()
+                        .to_uppercase(),
+                    None => "".to_string(),
                 },
                 x.name.to_string().to_uppercase(),
             )
@@ -147,12 +160,16 @@ impl Promptable<Project> for Link {
         };
         let org_name = match &item.organization {
             Some(o) => {
-                let name = o.name.as_ref().unwrap();
-                name.to_owned()
+                let name = o.name.to_owned().unwrap_or_default();
+                name
             }
             None => "Unnamed Project".to_string(),
         };
-        let project_name = format!("{} {}", org_name.to_uppercase().bold(), item.name.to_string());
+        let project_name = format!(
+            "{} {}",
+            org_name.to_uppercase().bold(),
+            item.name.to_string()
+        );
         match active {
             true => row![
                 index.to_string().bold(),
@@ -256,87 +273,170 @@ pub struct Link {
     pub service: String,
     pub hourtype: String,
     pub description: String,
+    /// Note template for this link, overriding the one in the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_template: Option<String>,
 }
 
 impl Link {
-    fn save(self) {
-        let mut links = Link::get_mapping();
+    fn save(self) -> Result<()> {
+        let mut links = Link::get_mapping()?;
         links.insert(self.alias.to_string(), self);
-        let toml_string = toml::to_string(&links).expect("Couldnt parse links");
-        let linksfile = dirs::home_dir()
-            .expect("Can't find homedir on this fs")
-            .join(".simpl/links.toml");
-        fs::write(linksfile, toml_string).expect("Failed to write config");
+        Link::store_mapping(&links)
+    }
+
+    fn remove(alias: String) -> Result<()> {
+        let mut links = Link::get_mapping()?;
+        links
+            .remove(&alias)
+            .ok_or_else(|| Error::UnknownAlias(alias.to_owned()))?;
+        Link::store_mapping(&links)
     }
 
-    fn remove(alias: String) {
-        let mut links = Link::get_mapping();
-        links.remove(&alias);
-        let toml_string = toml::to_string(&links).expect("Couldnt parse links");
-        let linksfile = dirs::home_dir()
-            .expect("Can't find homedir on this fs")
-            .join(".simpl/links.toml");
-        fs::write(linksfile, toml_string).expect("Failed to write config");
+    fn store_mapping(links: &HashMap<String, Link>) -> Result<()> {
+        let toml_string = toml::to_string(links).expect("Couldnt parse links");
+        let linksfile = simpl_path("links.toml")?;
+        if dryrun::enabled() {
+            dryrun::print_diff(&linksfile, &toml_string);
+            return Ok(());
+        }
+        fs::write(linksfile, toml_string)
+            .map_err(|err| Error::Io(format!("Failed to write links: {}", err)))
     }
 
-    fn from_prompt(show_all_projects: bool) -> Link {
+    fn from_prompt(show_all_projects: bool) -> Result<Link> {
         let project: Project = match show_all_projects {
-            true => Self::prompt(Some(Filter::All)),
-            false => Self::prompt(Some(Filter::Active)),
+            true => Self::prompt(Some(Filter::All))?,
+            false => Self::prompt(Some(Filter::Active))?,
         };
         std::env::set_var("PROJECT_ID", &project.id);
-        let service: Service = Self::prompt(None);
-        let hourtype: HourType = Self::prompt(None);
+        let service: Service = Self::prompt(None)?;
+        let hourtype: HourType = Self::prompt(None)?;
         let description = format!(
             "{} for {} - {}",
             &hourtype.label,
             &project.name,
             &service.name.unwrap_or("Unnamed Service".to_string())
         );
-        Link {
+        Ok(Link {
             project: project.id,
             service: service.id,
             hourtype: hourtype.id,
             alias: Input::with_theme(&ColorfulTheme::default())
                 .with_prompt("Enter an alias")
-                .interact()
-                .unwrap(),
+                .interact()?,
             description,
-        }
+            note_template: None,
+        })
     }
 
-    pub fn from_alias(alias: String) -> Link {
-        let links = Self::get_mapping();
+    pub fn from_alias(alias: String) -> Result<Link> {
+        let links = Self::get_mapping()?;
         match links.get(&alias) {
-            Some(x) => Link {
+            Some(x) => Ok(Link {
                 alias: x.alias.to_string(),
                 project: x.project.to_string(),
                 service: x.service.to_string(),
                 hourtype: x.hourtype.to_string(),
                 description: x.description.to_string(),
-            },
-            None => panic!("No link for alias"),
+                note_template: x.note_template.to_owned(),
+            }),
+            None => Err(Error::UnknownAlias(alias)),
+        }
+    }
+
+    /// Link booking on the same service and hour type as booked hours
+    pub fn from_hours(links: &[Link], hours: &Hours) -> Option<Link> {
+        let service = hours.projectservice.as_ref()?;
+        let hourtype = hours.hourtype.as_ref()?;
+        links
+            .iter()
+            .find(|link| link.service == service.id && link.hourtype == hourtype.id)
+            .cloned()
+    }
+
+    /// Lets the user pick a link, most recently booked first, narrowed down by a search
+    ///
+    /// Without a terminal to pick from, e.g. in a script, the alias is refused instead.
+    pub fn from_picker(query: Option<String>) -> Result<Link> {
+        if !atty::is(atty::Stream::Stdin) {
+            return Err(match query {
+                Some(alias) => Error::UnknownAlias(alias),
+                None => Error::Invalid(String::from(
+                    "No alias given, and there is no terminal to pick one in",
+                )),
+            });
+        }
+        let mut links = Self::get_links()?;
+        if links.is_empty() {
+            return Err(Error::Invalid(String::from(
+                "There are no links yet, add one with simpl links add",
+            )));
         }
+        sort_by_last_use(&mut links, &History::load()?);
+        let mut query = query;
+        let matches = loop {
+            let search = match query.take() {
+                Some(search) => search,
+                None => Input::with_theme(&ColorfulTheme::default())
+                    .with_prompt("Search links (empty for all)")
+                    .default(String::new())
+                    .show_default(false)
+                    .interact()?,
+            };
+            let matches: Links = links
+                .iter()
+                .filter(|link| link.matches(&search))
+                .cloned()
+                .collect();
+            match matches.is_empty() {
+                true => output::message(format!("{} '{}'", "No links match".yellow(), search)),
+                false => break matches,
+            }
+        };
+        let items: Vec<String> = matches
+            .iter()
+            .map(|link| format!("{} - {}", link.alias, link.description))
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select a link")
+            .items(&items)
+            .default(0)
+            .interact()?;
+        matches
+            .into_iter()
+            .nth(selection)
+            .ok_or_else(|| Error::Invalid(format!("{} is not one of the options", selection)))
     }
 
-    fn get_links() -> Links {
-        let link_map = Self::get_mapping();
+    /// Whether the letters of the query appear in order in the alias or description
+    fn matches(&self, query: &str) -> bool {
+        let text = format!("{} {}", self.alias, self.description).to_lowercase();
+        let mut chars = text.chars();
+        query
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .all(|c| chars.any(|t| t == c))
+    }
+
+    pub fn get_links() -> Result<Links> {
+        let link_map = Self::get_mapping()?;
         let mut links = vec![];
         for (_, link) in link_map.iter() {
             links.push(link.clone());
         }
-        links
+        Ok(links)
     }
 
-    fn get_mapping() -> HashMap<String, Link> {
-        let linksfile = dirs::home_dir()
-            .expect("Can't find home directory on this filesystem")
-            .join(".simpl/links.toml");
+    pub fn get_mapping() -> Result<HashMap<String, Link>> {
+        let linksfile = simpl_path("links.toml")?;
         let links: HashMap<String, Link> = match fs::read_to_string(&linksfile) {
-            Ok(string) => toml::from_str(&string.to_string().to_owned()).unwrap(),
+            Ok(string) => toml::from_str(&string.to_string().to_owned())
+                .map_err(|err| Error::ConfigInvalid(format!("links.toml: {}", err)))?,
             Err(_) => HashMap::new(),
         };
-        links
+        Ok(links)
     }
 }
 
@@ -382,6 +482,26 @@ impl TableDisplay<Link> for Links {
     }
 }
 
+/// Sorts links by when they were last booked according to the history, most recent first and
+/// never booked ones by alias at the end
+fn sort_by_last_use(links: &mut [Link], history: &History) {
+    let mut last_used = HashMap::new();
+    for entry in history.entries.iter() {
+        let used = last_used
+            .entry(entry.alias.as_str())
+            .or_insert(entry.timestamp);
+        if entry.timestamp > *used {
+            *used = entry.timestamp;
+        }
+    }
+    links.sort_by(|a, b| {
+        last_used
+            .get(b.alias.as_str())
+            .cmp(&last_used.get(a.alias.as_str()))
+            .then_with(|| a.alias.cmp(&b.alias))
+    });
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "links")]
 pub enum LinkCommand {
@@ -400,14 +520,14 @@ pub enum LinkCommand {
 }
 
 impl LinkCommand {
-    pub fn execute(&self) {
-        init_config_env();
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
         match self {
             LinkCommand::Add { show_all } => {
-                let new_link = Link::from_prompt(*show_all);
+                let new_link = Link::from_prompt(*show_all)?;
                 let description = &new_link.description.to_owned();
                 let alias = &new_link.alias.to_owned();
-                new_link.save();
+                new_link.save()?;
                 println!(
                     "Succesfully added link alias {} for {}",
                     alias.bold().green(),
@@ -415,13 +535,71 @@ impl LinkCommand {
                 );
             }
             LinkCommand::Remove { alias } => {
-                Link::remove(alias.to_string());
+                Link::remove(alias.to_string())?;
                 println!("Succesfully removed link alias {}", alias.green());
             }
+            LinkCommand::Show if output::json() => {
+                let links: BTreeMap<String, Link> = Link::get_mapping()?.into_iter().collect();
+                output::print_json(&links);
+            }
             LinkCommand::Show => {
-                let links: Links = Link::get_links();
+                let links: Links = Link::get_links()?;
                 Links::print_table(&links);
             }
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryEntry;
+
+    fn link(alias: &str, description: &str) -> Link {
+        Link {
+            alias: alias.to_string(),
+            project: String::from("project:1"),
+            service: String::from("service:1"),
+            hourtype: String::from("hourtype:1"),
+            description: description.to_string(),
+            note_template: None,
+        }
+    }
+
+    fn entry(alias: &str, timestamp: &str) -> HistoryEntry {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": timestamp,
+            "alias": alias,
+            "id": null,
+            "hours": 1.0,
+            "start_date": timestamp,
+            "note": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn sorts_recently_booked_links_first() {
+        let mut links = vec![link("c", ""), link("b", ""), link("a", ""), link("d", "")];
+        let history = History {
+            entries: vec![
+                entry("b", "2019-11-12T09:00:00"),
+                entry("d", "2019-11-11T09:00:00"),
+                entry("d", "2019-11-13T09:00:00"),
+            ],
+        };
+        sort_by_last_use(&mut links, &history);
+        let aliases: Vec<&str> = links.iter().map(|link| link.alias.as_str()).collect();
+        assert_eq!(aliases, vec!["d", "b", "a", "c"]);
+    }
+
+    #[test]
+    fn matches_letters_in_order() {
+        let link = link("labd-dev", "Lab Digital development");
+        assert!(link.matches("ldev"));
+        assert!(link.matches("Lab Digital"));
+        assert!(link.matches(""));
+        assert!(!link.matches("ved"));
     }
 }
@@ -1,35 +1,122 @@
+mod api;
+mod batch;
 mod book;
+mod capacity;
 mod config;
+mod copy;
+mod dates;
+mod dryrun;
+mod duration;
+mod error;
+mod fill;
+mod git;
+mod history;
+mod hours;
 mod links;
+mod output;
+mod preflight;
+mod queue;
+mod recurring;
 mod show;
+mod suggest;
+mod timer;
 use structopt::StructOpt;
 #[macro_use]
 extern crate prettytable;
 
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Print what would be sent to the API or written to disk without doing so
+    #[structopt(long = "dry-run", global = true)]
+    dry_run: bool,
+    /// Print results as text or as JSON, e.g. to pipe them into jq
+    #[structopt(
+        long = "output",
+        global = true,
+        default_value = "text",
+        possible_values = &["text", "json"]
+    )]
+    output: output::Format,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
 #[derive(Debug, StructOpt)]
 enum Command {
     /// Manage established links
     Links(links::LinkCommand),
     /// Book hours under aliased service
     Book(book::BookCommand),
-    /// Create simpl config
+    /// Create simpl config, or print it with --output json
     Config(config::ConfigCommand),
     /// Display worked hours
     Show(show::ShowCommand),
+    /// Edit or remove booked hours
+    Hours(hours::HoursCommand),
+    /// Remove the most recent booking
+    Undo(history::UndoCommand),
+    /// Display the journal of booking attempts
+    History(history::HistoryCommand),
+    /// Start a timer for an aliased service
+    Start(timer::StartCommand),
+    /// Stop the running timer and book the elapsed hours
+    Stop(timer::StopCommand),
+    /// Book the running timer and start a new one for another alias
+    Switch(timer::SwitchCommand),
+    /// Show the running timer
+    Status(timer::StatusCommand),
+    /// Pause the running timer
+    Pause(timer::PauseCommand),
+    /// Resume a paused timer
+    Resume(timer::ResumeCommand),
+    /// Manage hours waiting to be synced
+    Queue(queue::QueueCommand),
+    /// Book the queued hours
+    Sync(queue::SyncCommand),
+    /// Copy booked hours from another day or week
+    Copy(copy::CopyCommand),
+    /// Book entries that recur on a fixed schedule
+    Recurring(recurring::RecurringCommand),
+    /// Propose bookings from your git commits
+    Suggest(suggest::SuggestCommand),
+    /// Book the hours left to reach the daily target
+    Fill(fill::FillCommand),
 }
 
 impl Command {
-    fn execute(&self) {
+    fn execute(&self) -> error::Result<()> {
         match self {
             Command::Config(cmd) => cmd.execute(),
             Command::Links(cmd) => cmd.execute(),
             Command::Book(cmd) => cmd.execute(),
             Command::Show(cmd) => cmd.execute(),
+            Command::Hours(cmd) => cmd.execute(),
+            Command::Undo(cmd) => cmd.execute(),
+            Command::History(cmd) => cmd.execute(),
+            Command::Start(cmd) => cmd.execute(),
+            Command::Stop(cmd) => cmd.execute(),
+            Command::Switch(cmd) => cmd.execute(),
+            Command::Status(cmd) => cmd.execute(),
+            Command::Pause(cmd) => cmd.execute(),
+            Command::Resume(cmd) => cmd.execute(),
+            Command::Queue(cmd) => cmd.execute(),
+            Command::Sync(cmd) => cmd.execute(),
+            Command::Copy(cmd) => cmd.execute(),
+            Command::Recurring(cmd) => cmd.execute(),
+            Command::Suggest(cmd) => cmd.execute(),
+            Command::Fill(cmd) => cmd.execute(),
         }
     }
 }
 
 fn main() {
-    let opt = Command::from_args();
-    opt.execute();
+    let opt = Opt::from_args();
+    if opt.dry_run {
+        std::env::set_var("SIMPL_DRY_RUN", "1");
+    }
+    output::set_format(opt.output);
+    if let Err(err) = opt.command.execute() {
+        err.report();
+        std::process::exit(err.exit_code());
+    }
 }
@@ -0,0 +1,102 @@
+use serde::Serialize;
+use serde_json::{to_string_pretty, to_value, Value};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::str::FromStr;
+
+/// Format of the results printed by commands, set with the global `--output` option
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Format, String> {
+        match value {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            other => Err(format!(
+                "'{}' is not an output format, use text or json",
+                other
+            )),
+        }
+    }
+}
+
+thread_local! {
+    /// Format given with `--output`, set once per run before the command executes
+    static FORMAT: Cell<Format> = Cell::new(Format::Text);
+}
+
+/// Prints the results of the rest of the run in `format`
+pub fn set_format(format: Format) {
+    FORMAT.with(|cell| cell.set(format));
+}
+
+/// Whether `--output json` was given
+pub fn json() -> bool {
+    FORMAT.with(Cell::get) == Format::Json
+}
+
+thread_local! {
+    /// Results printed inside `as_list`, printed together once it returns
+    static LIST: RefCell<Option<Vec<Value>>> = RefCell::new(None);
+}
+
+/// Prints a result as JSON on stdout
+pub fn print_json<T: Serialize>(value: &T) {
+    let value = to_value(value).expect("Could not encode JSON output");
+    let value = LIST.with(|list| match list.borrow_mut().as_mut() {
+        Some(list) => {
+            list.push(value);
+            None
+        }
+        None => Some(value),
+    });
+    if let Some(value) = value {
+        println!(
+            "{}",
+            to_string_pretty(&value).expect("Could not encode JSON output")
+        );
+    }
+}
+
+/// Runs `f`, printing the results it prints as a single JSON array, also when `f` fails
+pub fn as_list<T, F: FnOnce() -> T>(f: F) -> T {
+    if !json() {
+        return f();
+    }
+    LIST.with(|list| list.replace(Some(vec![])));
+    let result = f();
+    let values = LIST.with(|list| list.replace(None)).unwrap_or_default();
+    print_json(&values);
+    result
+}
+
+/// Prints a message for the user, which goes to stderr in JSON mode to keep stdout parseable
+pub fn message<T: fmt::Display>(text: T) {
+    match json() {
+        true => eprintln!("{}", text),
+        false => println!("{}", text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!("text".parse::<Format>(), Ok(Format::Text));
+        assert_eq!("json".parse::<Format>(), Ok(Format::Json));
+        assert_eq!(
+            "csv".parse::<Format>(),
+            Err(String::from(
+                "'csv' is not an output format, use text or json"
+            ))
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::api::fetch;
+use crate::links::Link;
+use crate::output;
+use chrono::NaiveDate;
+use colored::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Deserialize)]
+struct ProjectStatus {
+    label: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectWindow {
+    name: Option<String>,
+    project_status: Option<ProjectStatus>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ServiceWindow {
+    name: Option<String>,
+    status: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    write_hours_start_date: Option<String>,
+    write_hours_end_date: Option<String>,
+}
+
+/// Reads the day of a date given by the API, which may or may not include a time
+fn parse_day(value: &Option<String>) -> Option<NaiveDate> {
+    let value = value.as_ref()?;
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Checks that `day` lies within the window opening at `start` and closing at `end`
+fn check_window(
+    subject: &str,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    day: NaiveDate,
+) -> Result<(), String> {
+    if let Some(start) = start {
+        if day < start {
+            return Err(format!("{} opens on {}", subject, start));
+        }
+    }
+    if let Some(end) = end {
+        if day > end {
+            return Err(format!("{} closed on {}", subject, end));
+        }
+    }
+    Ok(())
+}
+
+fn check_project(project: &ProjectWindow, day: NaiveDate) -> Result<(), String> {
+    let subject = format!(
+        "project {}",
+        project
+            .name
+            .to_owned()
+            .unwrap_or("Unnamed project".to_string())
+    );
+    if let Some(status) = &project.project_status {
+        if status.label != "tab_pactive" {
+            return Err(format!("{} is not active ({})", subject, status.label));
+        }
+    }
+    check_window(
+        &subject,
+        parse_day(&project.start_date),
+        parse_day(&project.end_date),
+        day,
+    )
+}
+
+fn check_service(service: &ServiceWindow, day: NaiveDate) -> Result<(), String> {
+    let subject = format!(
+        "service {}",
+        service
+            .name
+            .to_owned()
+            .unwrap_or("Unnamed Service".to_string())
+    );
+    if let Some(status) = &service.status {
+        if status != "open" {
+            return Err(format!("{} is {}", subject, status));
+        }
+    }
+    check_window(
+        &subject,
+        parse_day(&service.start_date),
+        parse_day(&service.end_date),
+        day,
+    )?;
+    let write_start = parse_day(&service.write_hours_start_date);
+    let write_end = parse_day(&service.write_hours_end_date);
+    if write_start.is_some() != write_end.is_some() {
+        return Err(format!(
+            "{} has only one of its write hours dates set, fill both or neither in Simplicate",
+            subject
+        ));
+    }
+    check_window(
+        &format!("writing hours on {}", subject),
+        write_start,
+        write_end,
+        day,
+    )
+}
+
+/// Records fetched during this run by path, with the reason when fetching failed
+type Cache<T> = RefCell<HashMap<String, Rc<Result<T, String>>>>;
+
+thread_local! {
+    /// Projects fetched during this run, so a batch on the same link fetches them once
+    static PROJECTS: Cache<ProjectWindow> = RefCell::new(HashMap::new());
+    /// Services fetched during this run
+    static SERVICES: Cache<ServiceWindow> = RefCell::new(HashMap::new());
+}
+
+/// Fetches the record at `path` once per run, remembering failures as well
+fn fetch_cached<T: DeserializeOwned>(
+    cache: &'static std::thread::LocalKey<Cache<T>>,
+    path: String,
+) -> Rc<Result<T, String>> {
+    if let Some(cached) = cache.with(|cache| cache.borrow().get(&path).cloned()) {
+        return cached;
+    }
+    let fetched = Rc::new(fetch(&path));
+    cache.with(|cache| cache.borrow_mut().insert(path, fetched.clone()));
+    fetched
+}
+
+/// Warns that a check couldn't be made, leaving the API to decide
+fn warn_unchecked(subject: &str, reason: &str) {
+    output::message(format!(
+        "{} {}: {}",
+        "Could not check".yellow(),
+        subject,
+        reason.yellow()
+    ));
+}
+
+/// Verifies that the project and service of a link accept hours on the given day
+///
+/// A project or service that can't be fetched is reported and left for the API to decide on.
+pub fn check_booking(link: &Link, day: NaiveDate) -> Result<(), String> {
+    let project = fetch_cached(&PROJECTS, format!("projects/project/{}", link.project));
+    match project.as_ref() {
+        Ok(project) => check_project(project, day)?,
+        Err(reason) => warn_unchecked(&format!("project of {}", link.alias), reason),
+    }
+    let service = fetch_cached(&SERVICES, format!("projects/service/{}", link.service));
+    match service.as_ref() {
+        Ok(service) => check_service(service, day)?,
+        Err(reason) => warn_unchecked(&format!("service of {}", link.alias), reason),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    fn service(start: Option<&str>, end: Option<&str>) -> ServiceWindow {
+        ServiceWindow {
+            name: Some(String::from("Development")),
+            status: Some(String::from("open")),
+            start_date: None,
+            end_date: None,
+            write_hours_start_date: start.map(String::from),
+            write_hours_end_date: end.map(String::from),
+        }
+    }
+
+    #[test]
+    fn reads_days_with_and_without_time() {
+        assert_eq!(
+            parse_day(&Some(String::from("2019-11-11 00:00:00"))),
+            Some(day("2019-11-11"))
+        );
+        assert_eq!(
+            parse_day(&Some(String::from("2019-11-11"))),
+            Some(day("2019-11-11"))
+        );
+        assert_eq!(parse_day(&Some(String::from("soon"))), None);
+        assert_eq!(parse_day(&None), None);
+    }
+
+    #[test]
+    fn checks_the_write_hours_window() {
+        let open = service(Some("2019-11-01"), Some("2019-11-30"));
+        assert!(check_service(&open, day("2019-11-01")).is_ok());
+        assert!(check_service(&open, day("2019-11-30")).is_ok());
+        assert!(check_service(&open, day("2019-10-31")).is_err());
+        assert!(check_service(&open, day("2019-12-01")).is_err());
+        assert!(check_service(&service(None, None), day("2019-12-01")).is_ok());
+        assert!(check_service(&service(Some("2019-11-01"), None), day("2019-11-11")).is_err());
+    }
+
+    #[test]
+    fn refuses_services_that_are_not_open() {
+        let mut closed = service(None, None);
+        closed.status = Some(String::from("closed"));
+        assert!(check_service(&closed, day("2019-11-11")).is_err());
+    }
+}
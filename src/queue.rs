@@ -0,0 +1,340 @@
+use crate::api::{created_id, Outcome};
+use crate::book::{new_hours, post_hours, print_booked};
+use crate::config::{init_config_env, init_timezone, simpl_path};
+use crate::dates;
+use crate::dryrun;
+use crate::error::{Error, Result};
+use crate::history::History;
+use crate::links::Link;
+use crate::output;
+use chrono::NaiveDateTime;
+use colored::*;
+use prettytable::Table;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use structopt::StructOpt;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct QueuedHours {
+    pub hours: f64,
+    pub start_date: NaiveDateTime,
+    pub note: String,
+    pub queued_at: NaiveDateTime,
+    /// Error response of the API when it refused these hours, which are then not synced again
+    ///
+    /// Kept as a JSON string, as TOML can't hold every JSON value (e.g. `null`).
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "json_string")]
+    pub rejected: Option<Value>,
+    pub link: Link,
+}
+
+mod json_string {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::Value;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Json(String),
+        /// Queues written before the response was stored as a string
+        Table(Value),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Option<Value>, s: S) -> Result<S::Ok, S::Error> {
+        value.as_ref().map(Value::to_string).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Value>, D::Error> {
+        Ok(
+            Option::<Stored>::deserialize(d)?.map(|stored| match stored {
+                Stored::Json(json) => serde_json::from_str(&json).unwrap_or(Value::String(json)),
+                Stored::Table(value) => value,
+            }),
+        )
+    }
+}
+
+impl QueuedHours {
+    pub fn new(link: &Link, hours: f64, start_date: NaiveDateTime, note: String) -> QueuedHours {
+        QueuedHours {
+            hours,
+            start_date,
+            note,
+            queued_at: dates::now(),
+            link: link.clone(),
+            rejected: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Queue {
+    pub entries: Vec<QueuedHours>,
+}
+
+impl Queue {
+    pub fn load() -> Result<Queue> {
+        let queuefile = simpl_path("queue.toml")?;
+        match fs::read_to_string(queuefile) {
+            Ok(string) => toml::from_str(&string)
+                .map_err(|err| Error::ConfigInvalid(format!("queue.toml: {}", err))),
+            Err(_) => Ok(Queue::default()),
+        }
+    }
+
+    fn store(&self) -> Result<()> {
+        let simpldir = simpl_path("")?;
+        let toml_string = toml::to_string(self)
+            .map_err(|err| Error::Io(format!("Failed to encode queue: {}", err)))?;
+        if dryrun::enabled() {
+            dryrun::print_diff(&simpldir.join("queue.toml"), &toml_string);
+            return Ok(());
+        }
+        fs::create_dir_all(&simpldir)
+            .and_then(|_| fs::write(simpldir.join("queue.toml"), toml_string))
+            .map_err(|err| Error::Io(format!("Failed to write queue: {}", err)))
+    }
+
+    pub fn push(entry: QueuedHours) -> Result<()> {
+        let mut queue = Queue::load()?;
+        output::message(format!(
+            "Queued {} hours for {}, run {} to book them",
+            entry.hours.to_string().yellow().italic(),
+            entry.link.alias.bold().green(),
+            "simpl sync".bold()
+        ));
+        queue.entries.push(entry);
+        queue.store()
+    }
+
+    fn print_table(&self) {
+        let mut table = Table::new();
+        table.add_row(row![
+            "Index".bold().yellow(),
+            "Alias".bold().yellow(),
+            "Date".bold().yellow(),
+            "Hours".bold().yellow(),
+            "Status".bold().yellow(),
+            "Note".bold().yellow()
+        ]);
+        for (index, entry) in self.entries.iter().enumerate() {
+            let status = match entry.rejected {
+                Some(_) => "rejected".red(),
+                None => "waiting".green(),
+            };
+            table.add_row(row![
+                index.to_string().bold(),
+                entry.link.alias.green().bold(),
+                entry.start_date.to_string().green(),
+                entry.hours.to_string().green(),
+                status,
+                entry.note.green()
+            ]);
+        }
+        table.printstd();
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "queue")]
+pub enum QueueCommand {
+    /// Show the hours waiting to be synced
+    #[structopt(name = "show")]
+    Show,
+    /// Remove queued hours by index
+    #[structopt(name = "rm")]
+    Remove { index: usize },
+}
+
+impl QueueCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_timezone()?;
+        let mut queue = Queue::load()?;
+        match self {
+            QueueCommand::Show => match queue.entries.is_empty() {
+                true => println!("{}", "The booking queue is empty".green()),
+                false => queue.print_table(),
+            },
+            QueueCommand::Remove { index } => {
+                if *index >= queue.entries.len() {
+                    return Err(Error::Invalid(String::from(
+                        "No queued hours at this index",
+                    )));
+                }
+                let entry = queue.entries.remove(*index);
+                queue.store()?;
+                println!(
+                    "Succesfully removed {} queued hours for {}",
+                    entry.hours.to_string().yellow(),
+                    entry.link.alias.green()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "sync")]
+pub struct SyncCommand {
+    /// Also retry the hours the API rejected before, e.g. after reopening their project
+    #[structopt(long = "retry-rejected")]
+    pub retry_rejected: bool,
+}
+
+impl SyncCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        output::as_list(|| self.sync())
+    }
+
+    /// Books the queued hours, keeping the ones that failed queued
+    fn sync(&self) -> Result<()> {
+        let queue = Queue::load()?;
+        if queue.entries.is_empty() {
+            output::message("The booking queue is empty".green());
+            return Ok(());
+        }
+        let mut remaining = vec![];
+        let mut reachable = true;
+        let mut skipped = 0;
+        let mut failed = 0;
+        for mut entry in queue.entries {
+            if entry.rejected.is_some() && !self.retry_rejected {
+                skipped += 1;
+                remaining.push(entry);
+                continue;
+            }
+            if !reachable {
+                failed += 1;
+                remaining.push(entry);
+                continue;
+            }
+            output::message(format!(
+                "{} {} hours for {} on {}",
+                "Syncing".bold(),
+                entry.hours.to_string().yellow(),
+                entry.link.alias.green(),
+                entry.start_date.to_string().green()
+            ));
+            let newhours = match new_hours(
+                &entry.link,
+                entry.hours,
+                entry.start_date,
+                entry.note.to_owned(),
+            ) {
+                Ok(newhours) => newhours,
+                Err(err) => {
+                    err.report();
+                    failed += 1;
+                    remaining.push(entry);
+                    continue;
+                }
+            };
+            let outcome = post_hours(&newhours);
+            History::record(&entry.link, &newhours, &outcome);
+            match outcome {
+                Outcome::Accepted(data) => {
+                    print_booked(&entry.link, &newhours, entry.hours, created_id(&data))
+                }
+                Outcome::Rejected(err) => {
+                    Error::Rejected(err.clone()).report();
+                    entry.rejected = Some(err);
+                    failed += 1;
+                    remaining.push(entry);
+                }
+                Outcome::Unreachable(reason) => {
+                    output::message(format!(
+                        "{}: {}",
+                        "Failed to reach the API".red(),
+                        reason.yellow()
+                    ));
+                    failed += 1;
+                    remaining.push(entry);
+                    reachable = false;
+                }
+            }
+        }
+        let queue = Queue { entries: remaining };
+        queue.store()?;
+        if skipped > 0 {
+            output::message(format!(
+                "{} rejected entries were skipped, remove them with {} or retry them with {}",
+                skipped.to_string().yellow().bold(),
+                "simpl queue rm <index>".bold(),
+                "simpl sync --retry-rejected".bold()
+            ));
+        }
+        // skipped rejections stay queued as well, so they also leave the sync incomplete
+        match failed + skipped {
+            0 => {
+                output::message("All queued hours are synced".green().bold());
+                Ok(())
+            }
+            n => {
+                output::message(format!(
+                    "{} entries remain queued, inspect them with {}",
+                    n.to_string().yellow().bold(),
+                    "simpl queue show".bold()
+                ));
+                Err(Error::Incomplete(n))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn queue(rejected: Option<Value>) -> Queue {
+        let link = Link {
+            alias: String::from("web"),
+            project: String::from("project:1"),
+            service: String::from("service:1"),
+            hourtype: String::from("hourtype:1"),
+            description: String::from("Website / Development"),
+            note_template: None,
+        };
+        let start_date = NaiveDateTime::parse_from_str("2020-03-02 09:00", "%Y-%m-%d %H:%M");
+        let mut entry = QueuedHours::new(&link, 1.5, start_date.unwrap(), String::from("login"));
+        entry.rejected = rejected;
+        Queue {
+            entries: vec![entry],
+        }
+    }
+
+    #[test]
+    fn stores_any_rejection() {
+        let rejections = vec![
+            None,
+            Some(json!("API responded with 404 Not Found")),
+            Some(json!(["project is closed", "service is closed"])),
+            Some(json!({"hours": null, "message": "project is closed"})),
+        ];
+        for rejected in rejections {
+            let stored = toml::to_string(&queue(rejected.clone())).unwrap();
+            let loaded: Queue = toml::from_str(&stored).unwrap();
+            assert_eq!(
+                loaded.entries[0].rejected, rejected,
+                "storing {:?}",
+                rejected
+            );
+        }
+    }
+
+    #[test]
+    fn loads_rejections_stored_as_tables() {
+        let stored = toml::to_string(&queue(None)).unwrap().replace(
+            "[entries.link]",
+            "[entries.rejected]\nmessage = \"project is closed\"\n\n[entries.link]",
+        );
+        let loaded: Queue = toml::from_str(&stored).unwrap();
+        assert_eq!(
+            loaded.entries[0].rejected,
+            Some(json!({"message": "project is closed"}))
+        );
+    }
+}
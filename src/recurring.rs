@@ -0,0 +1,292 @@
+use crate::book::{book_hours, format_note, is_same_booking, take_booked, Overrides};
+use crate::config::{init_config_env, UserConfig};
+use crate::dates::{self, DateExpr};
+use crate::duration::{round_hours, Duration};
+use crate::error::{self, Error};
+use crate::hours::booked_at;
+use crate::links::Link;
+use crate::output;
+use crate::show::fetch_employee_hours;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use colored::*;
+use prettytable::Table;
+use serde::{Deserialize, Serialize};
+use simplicate::structures::Hours;
+use std::collections::HashMap;
+use structopt::StructOpt;
+
+/// An entry booked on a fixed schedule, e.g. the daily standup
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recurring {
+    pub alias: String,
+    /// Time spent, written like the time of `simpl book` (e.g. `15m` or `0.25`)
+    pub duration: String,
+    pub note: Option<String>,
+    /// Days to book on: `daily`, `weekdays`, a range like `mon-fri` or a list like `mon,wed`
+    pub days: String,
+    /// Time of day the entry starts at (HH:MM), 09:00 when empty
+    pub time: Option<String>,
+}
+
+/// A recurring entry with its link, hours and days resolved
+struct Schedule {
+    link: Link,
+    hours: f64,
+    note: Option<String>,
+    days: Vec<Weekday>,
+    time: NaiveTime,
+}
+
+fn parse_day(value: &str) -> Result<Weekday, String> {
+    dates::parse_weekday(value.trim()).ok_or_else(|| format!("'{}' is not a weekday", value))
+}
+
+/// Parses `daily`, `weekdays`, ranges like `mon-fri` and lists like `mon,wed,fri`
+fn parse_days(value: &str) -> Result<Vec<Weekday>, String> {
+    let value = value.trim().to_lowercase();
+    let value = match value.as_str() {
+        "daily" => "mon-sun",
+        "weekdays" => "mon-fri",
+        other => other,
+    };
+    let mut days = vec![];
+    for part in value.split(',') {
+        let mut bounds = part.splitn(2, '-');
+        let first = parse_day(bounds.next().unwrap_or(""))?;
+        let last = match bounds.next() {
+            Some(last) => parse_day(last)?,
+            None => first,
+        };
+        if last.num_days_from_monday() < first.num_days_from_monday() {
+            return Err(format!("'{}' ends before it starts", part));
+        }
+        let mut day = first;
+        loop {
+            if !days.contains(&day) {
+                days.push(day);
+            }
+            if day == last {
+                break;
+            }
+            day = day.succ();
+        }
+    }
+    Ok(days)
+}
+
+impl Recurring {
+    fn schedule(&self, links: &HashMap<String, Link>) -> Result<Schedule, String> {
+        let link = links
+            .get(&self.alias)
+            .ok_or_else(|| format!("no link for alias '{}'", self.alias))?;
+        let duration: Duration = self.duration.parse()?;
+        let time = match &self.time {
+            Some(time) => dates::parse_time(time)?,
+            None => NaiveTime::from_hms(9, 0, 0),
+        };
+        Ok(Schedule {
+            link: link.clone(),
+            hours: duration.hours(),
+            note: self.note.to_owned(),
+            days: parse_days(&self.days)?,
+            time,
+        })
+    }
+}
+
+/// Validates all recurring entries from the config, printing the ones that are invalid
+fn schedules() -> error::Result<Vec<Schedule>> {
+    let recurring = UserConfig::load()?.recurring;
+    let links = Link::get_mapping()?;
+    let mut schedules = vec![];
+    let mut errors = vec![];
+    for (index, entry) in recurring.iter().enumerate() {
+        match entry.schedule(&links) {
+            Ok(schedule) => schedules.push(schedule),
+            Err(err) => errors.push(format!("entry {}: {}", index + 1, err)),
+        }
+    }
+    if !errors.is_empty() {
+        println!(
+            "{}",
+            "Fix these recurring entries in the config first:".red()
+        );
+        for err in errors.iter() {
+            println!("  {}", err.yellow());
+        }
+        return Err(Error::ConfigInvalid(format!(
+            "{} recurring entries are invalid",
+            errors.len()
+        )));
+    }
+    Ok(schedules)
+}
+
+/// Takes the booked hours of a schedule from `booked`, matched by day, service, hour type and
+/// hours and by either the note or the time, so identical entries each need their own booking
+fn take_booked_on(
+    booked: &mut Vec<Hours>,
+    start_date: NaiveDateTime,
+    schedule: &Schedule,
+    rounded: f64,
+    note: &str,
+) -> bool {
+    take_booked(booked, |hours| {
+        let booked_date = match booked_at(hours) {
+            Some(date) if date.date() == start_date.date() => date,
+            _ => return false,
+        };
+        let same_note = hours.note.as_deref() == Some(note);
+        is_same_booking(hours, &schedule.link, rounded)
+            && (same_note || booked_date.time() == start_date.time())
+    })
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "recurring")]
+pub enum RecurringCommand {
+    /// Show the recurring entries from the config
+    #[structopt(name = "show")]
+    Show,
+    /// Book the recurring entries that are not booked yet
+    #[structopt(name = "apply")]
+    Apply {
+        /// Day to book on (YYYY-MM-DD, today, yesterday, mon, last fri or -2d), today by default
+        #[structopt(short = "d", allow_hyphen_values = true)]
+        date: Option<DateExpr>,
+        /// Book every day of the week of the date
+        #[structopt(long = "week")]
+        week: bool,
+        /// Book even when the hours exceed the maximum hours of the day or week
+        #[structopt(long = "over-limit")]
+        over_limit: bool,
+    },
+}
+
+impl RecurringCommand {
+    fn show() -> error::Result<()> {
+        let schedules = schedules()?;
+        let mut table = Table::new();
+        table.add_row(row![
+            "Alias".bold().yellow(),
+            "Hours".bold().yellow(),
+            "Days".bold().yellow(),
+            "Time".bold().yellow(),
+            "Note".bold().yellow()
+        ]);
+        for schedule in schedules {
+            let days: Vec<String> = schedule.days.iter().map(|day| day.to_string()).collect();
+            table.add_row(row![
+                schedule.link.alias.green().bold(),
+                schedule.hours.to_string().green(),
+                days.join(",").green(),
+                schedule.time.format("%H:%M").to_string().green(),
+                schedule.note.to_owned().unwrap_or_default().green()
+            ]);
+        }
+        table.printstd();
+        Ok(())
+    }
+
+    fn apply(date: &Option<DateExpr>, week: bool, over_limit: bool) -> error::Result<()> {
+        let schedules = schedules()?;
+        if schedules.is_empty() {
+            output::message(
+                "No recurring entries configured, add them as [[recurring]] in the config".yellow(),
+            );
+            return Ok(());
+        }
+        let day = match date {
+            Some(dt) => dt.date(),
+            None => dates::today(),
+        };
+        let (start, end) = match week {
+            true => {
+                let start = dates::week_start(day);
+                (start, start + chrono::Duration::days(6))
+            }
+            false => (day, day),
+        };
+        let mut booked = fetch_employee_hours(start.and_hms(0, 0, 0), end.and_hms(23, 59, 59))?;
+        let mut skipped = 0;
+        let mut failed = 0;
+        let mut day = start;
+        while day <= end {
+            for schedule in schedules.iter().filter(|s| s.days.contains(&day.weekday())) {
+                let start_date = day.and_time(schedule.time);
+                let note = format_note(&schedule.link, start_date, &None, &schedule.note)?;
+                let rounded = round_hours(schedule.hours)?;
+                if take_booked_on(&mut booked, start_date, schedule, rounded, &note) {
+                    skipped += 1;
+                    continue;
+                }
+                // Already matched against the booked hours above, where identical entries each
+                // need their own booking
+                let overrides = Overrides {
+                    duplicate: true,
+                    over_limit,
+                };
+                if let Err(err) =
+                    book_hours(&schedule.link, schedule.hours, start_date, note, overrides)
+                {
+                    err.report();
+                    failed += 1;
+                }
+            }
+            day = day.succ();
+        }
+        if skipped > 0 {
+            output::message(format!(
+                "{} entries were already booked",
+                skipped.to_string().yellow().bold()
+            ));
+        }
+        match failed {
+            0 => {
+                output::message("All recurring entries are booked".green().bold());
+                Ok(())
+            }
+            n => Err(Error::Incomplete(n)),
+        }
+    }
+
+    pub fn execute(&self) -> error::Result<()> {
+        init_config_env()?;
+        match self {
+            RecurringCommand::Show => Self::show(),
+            RecurringCommand::Apply {
+                date,
+                week,
+                over_limit,
+            } => output::as_list(|| Self::apply(date, *week, *over_limit)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Weekday::*;
+
+    #[test]
+    fn parses_days() {
+        let cases = vec![
+            ("daily", vec![Mon, Tue, Wed, Thu, Fri, Sat, Sun]),
+            ("weekdays", vec![Mon, Tue, Wed, Thu, Fri]),
+            ("Tue-Thu", vec![Tue, Wed, Thu]),
+            ("mon,wed", vec![Mon, Wed]),
+            ("mon-tue,tue-wed", vec![Mon, Tue, Wed]),
+            ("fri", vec![Fri]),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_days(value), Ok(expected), "parsing '{}'", value);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_days() {
+        for value in &["", "fri-mon", "mon,", "someday", "mon-"] {
+            assert!(parse_days(value).is_err(), "parsing '{}'", value);
+        }
+    }
+}
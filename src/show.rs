@@ -1,53 +1,244 @@
+use crate::api::fetch_with;
 use crate::config::{init_config_env, init_simplicate_client};
-use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use crate::dates::{self, DateExpr};
+use crate::duration::round;
+use crate::error::{Error, Result};
+use crate::git::{find_tickets, ticket_pattern};
+use crate::output;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Weekday};
 use colored::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use simplicate::structures::Hours;
 use simplicate::QueryMany;
+use std::collections::HashMap;
 use std::env;
+use std::io;
+use std::str::FromStr;
 use structopt::StructOpt;
-use chrono::NaiveDateTime;
+
+/// Format of the hours printed by `show`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShowFormat {
+    Text,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for ShowFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<ShowFormat, String> {
+        match value {
+            "text" => Ok(ShowFormat::Text),
+            "csv" => Ok(ShowFormat::Csv),
+            "tsv" => Ok(ShowFormat::Tsv),
+            other => Err(format!("'{}' is not a format, use text, csv or tsv", other)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct OrganizationName {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProjectOrganization {
+    id: String,
+    organization: Option<OrganizationName>,
+}
+
+/// Hours fetched per request, the most the API returns at once
+const PAGE_SIZE: usize = 100;
+
+/// Projects looked up per request, which keeps the filter in the URL short
+const PROJECTS_PER_REQUEST: usize = 50;
+
+/// Names of the organizations of projects by project id, which the hours themselves don't include
+fn fetch_organizations(project_ids: &[String]) -> Result<HashMap<String, String>> {
+    let mut organizations = HashMap::new();
+    for ids in project_ids.chunks(PROJECTS_PER_REQUEST) {
+        let query = [(String::from("q[id][in]"), ids.join(","))];
+        let projects: Vec<ProjectOrganization> = fetch_with("projects/project", &query)
+            .map_err(|err| Error::Unreachable(format!("Failed to fetch organizations: {}", err)))?;
+        organizations.extend(
+            projects
+                .into_iter()
+                .filter_map(|project| Some((project.id, project.organization?.name?))),
+        );
+    }
+    Ok(organizations)
+}
+
+const EXPORT_HEADER: [&str; 8] = [
+    "date",
+    "start_time",
+    "project",
+    "organization",
+    "service",
+    "hourtype",
+    "hours",
+    "note",
+];
+
+/// Writes the header and one line per row, separated by `delimiter` and without colors
+fn write_rows<W: io::Write>(writer: W, rows: &[[String; 8]], delimiter: u8) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+    writer
+        .write_record(EXPORT_HEADER)
+        .map_err(|err| Error::Io(err.to_string()))?;
+    for row in rows {
+        writer
+            .write_record(row)
+            .map_err(|err| Error::Io(err.to_string()))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes one row per entry to stdout, with the organizations of their projects
+fn export(hours: &[Hours], tickets: &Regex, delimiter: u8) -> Result<()> {
+    let mut project_ids: Vec<String> = hours
+        .iter()
+        .filter_map(|h| h.project.as_ref().map(|p| p.id.to_owned()))
+        .collect();
+    project_ids.sort();
+    project_ids.dedup();
+    let organizations = fetch_organizations(&project_ids)?;
+    let rows: Vec<[String; 8]> = hours
+        .iter()
+        .map(|h| {
+            let organization = h
+                .project
+                .as_ref()
+                .and_then(|p| organizations.get(&p.id))
+                .cloned();
+            ShownHours::new(h, tickets).row(organization)
+        })
+        .collect();
+    write_rows(io::stdout(), &rows, delimiter)
+}
+
+/// Booked hours as printed with `--output json` and exported with `--format`
+#[derive(Serialize)]
+struct ShownHours {
+    id: String,
+    date: Option<String>,
+    time: Option<String>,
+    project: Option<String>,
+    service: Option<String>,
+    hourtype: Option<String>,
+    hours: f64,
+    note: Option<String>,
+    /// Ticket keys found in the note
+    tickets: Vec<String>,
+}
+
+impl ShownHours {
+    /// Booked hours with the tickets in their note matched by `tickets`
+    fn new(hours: &Hours, tickets: &Regex) -> ShownHours {
+        let mut start = hours
+            .start_date
+            .as_ref()
+            .map(|s| s.splitn(2, ' ').map(String::from).collect::<Vec<String>>())
+            .unwrap_or_default()
+            .into_iter();
+        ShownHours {
+            id: hours.id.to_owned(),
+            date: start.next(),
+            time: start.next(),
+            project: hours.project.as_ref().and_then(|p| p.name.to_owned()),
+            service: hours
+                .projectservice
+                .as_ref()
+                .and_then(|s| s.name.to_owned()),
+            hourtype: hours.hourtype.as_ref().map(|t| t.label.to_owned()),
+            hours: round(hours.hours),
+            note: hours.note.to_owned(),
+            tickets: find_tickets(tickets, hours.note.as_deref().unwrap_or("")),
+        }
+    }
+
+    /// Columns exported with `--format`, in the order of `EXPORT_HEADER`
+    fn row(self, organization: Option<String>) -> [String; 8] {
+        [
+            self.date.unwrap_or_default(),
+            self.time.unwrap_or_default(),
+            self.project.unwrap_or_default(),
+            organization.unwrap_or_default(),
+            self.service.unwrap_or_default(),
+            self.hourtype.unwrap_or_default(),
+            self.hours.to_string(),
+            self.note.unwrap_or_default(),
+        ]
+    }
+}
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "show")]
 pub struct ShowCommand {
-    /// Specify a start date time from when you want to view the hours (YYYY-MM-DDTHH-MM:SS)
-    /// defaults to last monday
-    #[structopt(short = "s")]
-    pub start_time: Option<NaiveDateTime>,
-
-    /// Specify a end date time till when you want to view the hours (YYYY-MM-DDTHH-MM:SS)
-    /// defaults to next saturday
-    #[structopt(short = "e")]
-    pub end_time: Option<NaiveDateTime>,
+    /// Specify a start date time from when you want to view the hours (YYYY-MM-DDTHH:MM:SS,
+    /// YYYY-MM-DD, today, yesterday, mon, last fri or -2d) defaults to last monday
+    #[structopt(short = "s", allow_hyphen_values = true)]
+    pub start_time: Option<DateExpr>,
+
+    /// Specify a end date time till when you want to view the hours (YYYY-MM-DDTHH:MM:SS,
+    /// YYYY-MM-DD, today, yesterday, mon, last fri or -2d) defaults to next saturday
+    #[structopt(short = "e", allow_hyphen_values = true)]
+    pub end_time: Option<DateExpr>,
+
+    /// Print the id of every entry, to use with the hours command
+    #[structopt(long = "ids")]
+    pub ids: bool,
+
+    /// Print the hours as colored text, or export them as csv or tsv rows with date, start
+    /// time, project, organization, service, hour type, hours and note
+    #[structopt(
+        long = "format",
+        default_value = "text",
+        possible_values = &["text", "csv", "tsv"]
+    )]
+    pub format: ShowFormat,
 }
 
 impl ShowCommand {
-    pub fn execute(&self) {
-        init_config_env();
-        let cli = init_simplicate_client();
-        let current_dt: NaiveDate = Utc::today().naive_local();
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let current_dt: NaiveDate = dates::today();
         let y = &current_dt.iso_week().year();
         let w = &current_dt.iso_week().week();
         let start_date = match self.start_time {
-            Some(dt) => dt,
+            Some(dt) => dt.at_start(),
             None => NaiveDate::from_isoywd(*y, *w, Weekday::Mon).and_hms(0, 0, 0),
         };
         let end_date = match self.end_time {
-            Some(dt) => dt,
+            Some(dt) => dt.at_end(),
             None => NaiveDate::from_isoywd(*y, *w, Weekday::Sat).and_hms(23, 59, 59),
         };
-        let params = vec![
-            (
-                String::from("q[employee.id]"),
-                env::var("SIMPL_EMPLOYEE_ID")
-                    .expect("No employee ID set")
-                    .to_string(),
-            ),
-            (String::from("q[start_date][ge]"), start_date.to_string()),
-            (String::from("q[start_date][le]"), end_date.to_string()),
-        ];
-        let mut hours: Vec<Hours> = Hours::fetch_many(cli, Some(params)).expect("No hours found");
+        if self.format != ShowFormat::Text && output::json() {
+            return Err(Error::Invalid(String::from(
+                "--format csv and tsv can't be combined with --output json",
+            )));
+        }
+        let mut hours: Vec<Hours> = fetch_employee_hours(start_date, end_date)?;
         hours.sort_by(|a, b| a.start_date.cmp(&b.start_date));
+        match self.format {
+            ShowFormat::Csv => return export(&hours, &ticket_pattern()?, b','),
+            ShowFormat::Tsv => return export(&hours, &ticket_pattern()?, b'\t'),
+            ShowFormat::Text => (),
+        }
+        if output::json() {
+            let tickets = ticket_pattern()?;
+            let shown: Vec<ShownHours> = hours
+                .iter()
+                .map(|hours| ShownHours::new(hours, &tickets))
+                .collect();
+            output::print_json(&shown);
+            return Ok(());
+        }
         let mut header = "unknown".to_string();
         let mut total: Vec<f64> = vec![];
         for h in hours {
@@ -72,6 +263,7 @@ impl ShowCommand {
                 }
                 None => (),
             };
+            let id = h.id.to_owned();
             let proj_name = match h.project {
                 Some(p) => p.name.unwrap_or("Unnamed project".to_string()),
                 None => "Unnamed project".to_string(),
@@ -100,7 +292,7 @@ impl ShowCommand {
                     note.yellow()
                 )
                 .to_string(),
-                time: ((h.hours * 100.0).round()) / 100.0,
+                time: round(h.hours),
                 updated_at: match h.start_date {
                     Some(s) => {
                         let mut elements = s.split(" ");
@@ -114,11 +306,16 @@ impl ShowCommand {
                 },
             };
             total.push(lh.time);
+            let id = match self.ids {
+                true => format!("\t({})", id).dimmed().to_string(),
+                false => String::from(""),
+            };
             println!(
-                "    {}\t\t{}\t{}",
+                "    {}\t\t{}\t{}{}",
                 lh.updated_at.italic().magenta(),
                 lh.time.to_string().bold().italic().green(),
                 lh.description,
+                id,
             );
         }
         if total.len() > 1 {
@@ -130,6 +327,33 @@ impl ShowCommand {
                 h_total.to_string().bold().green(),
             );
         };
+        Ok(())
+    }
+}
+
+/// Hours booked by the configured employee with a start date between `start_date` and `end_date`
+pub fn fetch_employee_hours(
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+) -> Result<Vec<Hours>> {
+    let employee_id = env::var("SIMPL_EMPLOYEE_ID").map_err(|_| Error::ConfigMissing)?;
+    let mut hours = vec![];
+    // the API returns a page of hours at a time, so keep asking until a page comes back short
+    loop {
+        let params = vec![
+            (String::from("q[employee.id]"), employee_id.to_owned()),
+            (String::from("q[start_date][ge]"), start_date.to_string()),
+            (String::from("q[start_date][le]"), end_date.to_string()),
+            (String::from("limit"), PAGE_SIZE.to_string()),
+            (String::from("offset"), hours.len().to_string()),
+        ];
+        let page = Hours::fetch_many(init_simplicate_client(), Some(params))
+            .ok_or_else(|| Error::Unreachable(String::from("Failed to fetch hours")))?;
+        let done = page.len() < PAGE_SIZE;
+        hours.extend(page);
+        if done {
+            return Ok(hours);
+        }
     }
 }
 
@@ -139,3 +363,73 @@ pub struct LoggedHour {
     pub time: f64,
     pub updated_at: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(note: &str) -> ShownHours {
+        ShownHours {
+            id: String::from("hours:1"),
+            date: Some(String::from("2020-03-02")),
+            time: Some(String::from("09:30:00")),
+            project: Some(String::from("Website")),
+            service: Some(String::from("Development")),
+            hourtype: Some(String::from("Regular")),
+            hours: 1.5,
+            note: Some(String::from(note)),
+            tickets: vec![],
+        }
+    }
+
+    fn exported(rows: &[[String; 8]], delimiter: u8) -> String {
+        let mut out = vec![];
+        write_rows(&mut out, rows, delimiter).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn exports_rows_in_header_order() {
+        let row = shown("ABC-12 login").row(Some(String::from("Acme")));
+        assert_eq!(
+            exported(&[row], b','),
+            "date,start_time,project,organization,service,hourtype,hours,note\n\
+             2020-03-02,09:30:00,Website,Acme,Development,Regular,1.5,ABC-12 login\n"
+        );
+    }
+
+    #[test]
+    fn leaves_missing_columns_empty() {
+        let mut hours = shown("");
+        hours.service = None;
+        hours.note = None;
+        assert_eq!(
+            hours.row(None),
+            [
+                "2020-03-02",
+                "09:30:00",
+                "Website",
+                "",
+                "",
+                "Regular",
+                "1.5",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_delimiters_and_quotes_in_notes() {
+        let note = "fix, \"urgent\"";
+        let csv = exported(&[shown(note).row(None)], b',');
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("2020-03-02,09:30:00,Website,,Development,Regular,1.5,\"fix, \"\"urgent\"\"\"")
+        );
+        let tsv = exported(&[shown(note).row(None)], b'\t');
+        assert_eq!(
+            tsv.lines().nth(1),
+            Some("2020-03-02\t09:30:00\tWebsite\t\tDevelopment\tRegular\t1.5\t\"fix, \"\"urgent\"\"\"")
+        );
+    }
+}
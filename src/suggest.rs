@@ -0,0 +1,294 @@
+use crate::book::{book_hours, format_note, prompt_booking, OverrideFlags};
+use crate::config::{init_config_env, Repository, UserConfig};
+use crate::dates::{self, DateExpr};
+use crate::duration::round;
+use crate::error::{Error, Result};
+use crate::git::{find_tickets, ticket_pattern, Commit};
+use crate::links::Link;
+use crate::queue::{Queue, QueuedHours};
+use chrono::{NaiveDate, NaiveDateTime};
+use colored::*;
+use dialoguer::{theme::ColorfulTheme, Checkboxes};
+use prettytable::Table;
+use regex::Regex;
+use structopt::StructOpt;
+
+/// Longest gap between two commits that still counts as working on the second one
+const MAX_GAP_MINUTES: i64 = 120;
+/// Time counted for the first commit after a longer break
+const FIRST_COMMIT_MINUTES: i64 = 30;
+
+/// Bookings proposed for the commits on one ticket in one repository on one day
+struct Suggestion {
+    link: Link,
+    day: NaiveDate,
+    ticket: Option<String>,
+    start_date: NaiveDateTime,
+    minutes: i64,
+    tickets: Vec<String>,
+    subjects: Vec<String>,
+}
+
+impl Suggestion {
+    fn hours(&self) -> f64 {
+        round(self.minutes as f64 / 60.0)
+    }
+
+    fn note(&self) -> Result<String> {
+        let tickets = match self.tickets.is_empty() {
+            true => None,
+            false => Some(self.tickets.to_owned()),
+        };
+        format_note(
+            &self.link,
+            self.start_date,
+            &tickets,
+            &Some(self.subjects.join("; ")),
+        )
+    }
+}
+
+/// Groups commits by day, repository and ticket, estimating the time spent from the gaps
+/// between consecutive commits
+fn suggest(mut commits: Vec<(Link, Commit)>, tickets: &Regex) -> Vec<Suggestion> {
+    commits.sort_by_key(|(_, commit)| commit.time);
+    let mut suggestions: Vec<Suggestion> = vec![];
+    let mut previous: Option<NaiveDateTime> = None;
+    for (link, commit) in commits {
+        let minutes = match previous {
+            Some(prev) if prev.date() == commit.time.date() => {
+                match (commit.time - prev).num_minutes() {
+                    gap if gap <= MAX_GAP_MINUTES => gap,
+                    _ => FIRST_COMMIT_MINUTES,
+                }
+            }
+            _ => FIRST_COMMIT_MINUTES,
+        };
+        previous = Some(commit.time);
+        let tickets = find_tickets(tickets, &commit.subject);
+        let ticket = tickets.first().cloned();
+        let day = commit.time.date();
+        let existing = suggestions
+            .iter_mut()
+            .find(|s| s.day == day && s.link.alias == link.alias && s.ticket == ticket);
+        let suggestion = match existing {
+            Some(suggestion) => suggestion,
+            None => {
+                suggestions.push(Suggestion {
+                    link,
+                    day,
+                    ticket,
+                    start_date: commit.time,
+                    minutes: 0,
+                    tickets: vec![],
+                    subjects: vec![],
+                });
+                suggestions.last_mut().unwrap()
+            }
+        };
+        suggestion.minutes += minutes;
+        for ticket in tickets {
+            if !suggestion.tickets.contains(&ticket) {
+                suggestion.tickets.push(ticket);
+            }
+        }
+        if !suggestion.subjects.contains(&commit.subject) {
+            suggestion.subjects.push(commit.subject);
+        }
+    }
+    suggestions.retain(|s| s.minutes > 0);
+    suggestions
+}
+
+fn print_suggestions(suggestions: &[Suggestion]) -> Result<()> {
+    let mut table = Table::new();
+    table.add_row(row![
+        "Index".bold().yellow(),
+        "Alias".bold().yellow(),
+        "Date".bold().yellow(),
+        "Hours".bold().yellow(),
+        "Note".bold().yellow()
+    ]);
+    for (index, suggestion) in suggestions.iter().enumerate() {
+        table.add_row(row![
+            index.to_string().bold(),
+            suggestion.link.alias.green().bold(),
+            suggestion.day.to_string().green(),
+            suggestion.hours().to_string().green(),
+            suggestion.note()?.green()
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "suggest")]
+pub struct SuggestCommand {
+    /// First day to look for commits (YYYY-MM-DD, today, yesterday, mon, last fri or -2d),
+    /// today by default
+    #[structopt(short = "s", allow_hyphen_values = true)]
+    pub start_time: Option<DateExpr>,
+    /// Last day to look for commits, the start day by default
+    #[structopt(short = "e", allow_hyphen_values = true)]
+    pub end_time: Option<DateExpr>,
+    /// Queue the accepted suggestions instead of booking them
+    #[structopt(long = "offline")]
+    pub offline: bool,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+}
+
+impl SuggestCommand {
+    fn commits(&self, repositories: &[Repository]) -> Result<Vec<(Link, Commit)>> {
+        let start = match self.start_time {
+            Some(dt) => dt.at_start(),
+            None => dates::today().and_hms(0, 0, 0),
+        };
+        let end = match (self.end_time, self.start_time) {
+            (Some(dt), _) | (None, Some(dt)) => dt.at_end(),
+            (None, None) => dates::today().and_hms(23, 59, 59),
+        };
+        let links = Link::get_mapping()?;
+        let mut commits = vec![];
+        for repository in repositories {
+            let link = match links.get(&repository.alias) {
+                Some(link) => link,
+                None => {
+                    println!(
+                        "{} {}: no link for alias '{}'",
+                        "Skipping".yellow(),
+                        repository.path,
+                        repository.alias
+                    );
+                    continue;
+                }
+            };
+            match repository.commits(start, end) {
+                Ok(found) => commits.extend(found.into_iter().map(|c| (link.clone(), c))),
+                Err(err) => println!("{} {}", "Skipping".yellow(), err),
+            }
+        }
+        Ok(commits)
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let repositories = UserConfig::from_fs()?
+            .map(|cfg| cfg.git.repositories)
+            .unwrap_or_default();
+        if repositories.is_empty() {
+            println!(
+                "{}",
+                "No repositories configured, add them as [[git.repositories]] with a path and alias"
+                    .yellow()
+            );
+            return Ok(());
+        }
+        let suggestions = suggest(self.commits(&repositories)?, &ticket_pattern()?);
+        if suggestions.is_empty() {
+            println!("{}", "No commits found to suggest bookings for".yellow());
+            return Ok(());
+        }
+        print_suggestions(&suggestions)?;
+        let items: Vec<(String, bool)> = suggestions
+            .iter()
+            .map(|s| (format!("{} {} {}", s.link.alias, s.day, s.hours()), true))
+            .collect();
+        let selected = Checkboxes::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select the suggestions to book")
+            .items_checked(&items)
+            .interact()?;
+        let mut failed = 0;
+        for index in selected {
+            let suggestion = &suggestions[index];
+            let (hours, note) = prompt_booking(
+                &suggestion.link,
+                suggestion.start_date,
+                suggestion.hours(),
+                suggestion.note()?,
+            )?;
+            let result = match self.offline {
+                true => Queue::push(QueuedHours::new(
+                    &suggestion.link,
+                    hours,
+                    suggestion.start_date,
+                    note,
+                )),
+                false => book_hours(
+                    &suggestion.link,
+                    hours,
+                    suggestion.start_date,
+                    note,
+                    self.checks.overrides(),
+                ),
+            };
+            if let Err(err) = result {
+                err.report();
+                failed += 1;
+            }
+        }
+        match failed {
+            0 => Ok(()),
+            n => Err(Error::Incomplete(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(alias: &str, time: &str, subject: &str) -> (Link, Commit) {
+        let link = Link {
+            alias: alias.to_string(),
+            project: String::from("project:1"),
+            service: String::from("service:1"),
+            hourtype: String::from("hourtype:1"),
+            description: String::from("Website / Development"),
+            note_template: None,
+        };
+        let commit = Commit {
+            time: NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap(),
+            subject: subject.to_string(),
+        };
+        (link, commit)
+    }
+
+    #[test]
+    fn groups_commits_by_day_repository_and_ticket() {
+        let commits = vec![
+            commit("web", "2020-03-02 14:00", "ABC-1 after lunch"),
+            commit("web", "2020-03-02 09:00", "ABC-1 start"),
+            commit("web", "2020-03-02 09:45", "ABC-1 more"),
+            commit("web", "2020-03-02 10:00", "ABC-2 other"),
+            commit("app", "2020-03-02 10:30", "no ticket"),
+            commit("web", "2020-03-03 09:00", "ABC-1 next day"),
+        ];
+        let suggestions = suggest(commits, &Regex::new(r"[A-Z]+-\d+").unwrap());
+        let summary: Vec<(&str, String, Option<&str>, f64)> = suggestions
+            .iter()
+            .map(|s| {
+                (
+                    s.link.alias.as_str(),
+                    s.day.to_string(),
+                    s.ticket.as_deref(),
+                    s.hours(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("web", String::from("2020-03-02"), Some("ABC-1"), 1.75),
+                ("web", String::from("2020-03-02"), Some("ABC-2"), 0.25),
+                ("app", String::from("2020-03-02"), None, 0.5),
+                ("web", String::from("2020-03-03"), Some("ABC-1"), 0.5),
+            ]
+        );
+        assert_eq!(
+            suggestions[0].subjects,
+            vec!["ABC-1 start", "ABC-1 more", "ABC-1 after lunch"]
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use crate::book::{book_hours, format_note, OverrideFlags, Overrides};
+use crate::config::{init_config_env, init_timezone, simpl_path};
+use crate::dates;
+use crate::dryrun;
+use crate::duration::{round, Duration};
+use crate::error::{Error, Result};
+use crate::links::Link;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use colored::*;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs;
+use structopt::StructOpt;
+
+/// Reads a moment in UTC, or a wall-clock time written by versions that stored those
+fn utc_or_local<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(moment) => Ok(moment.with_timezone(&Utc)),
+        Err(_) => value
+            .parse::<NaiveDateTime>()
+            .map(dates::utc)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+fn optional_utc_or_local<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<DateTime<Utc>>, D::Error> {
+    #[derive(Deserialize)]
+    struct Moment(#[serde(deserialize_with = "utc_or_local")] DateTime<Utc>);
+    Ok(Option::<Moment>::deserialize(deserializer)?.map(|Moment(moment)| moment))
+}
+
+/// A running or paused timer, its moments kept in UTC so daylight saving time or a change of
+/// the configured timezone doesn't change the elapsed time
+#[derive(Serialize, Deserialize)]
+pub struct Timer {
+    pub alias: String,
+    /// Moment the timer was first started, used as the start date of the booking
+    #[serde(deserialize_with = "utc_or_local")]
+    pub started_at: DateTime<Utc>,
+    /// Moment the timer was last started or resumed; empty while paused
+    #[serde(default, deserialize_with = "optional_utc_or_local")]
+    pub resumed_at: Option<DateTime<Utc>>,
+    /// Seconds tracked before the timer was last paused
+    pub elapsed: i64,
+}
+
+impl Timer {
+    fn new(alias: String) -> Timer {
+        let now = Utc::now();
+        Timer {
+            alias,
+            started_at: now,
+            resumed_at: Some(now),
+            elapsed: 0,
+        }
+    }
+
+    pub fn load() -> Result<Option<Timer>> {
+        let timerfile = simpl_path("timer.toml")?;
+        match fs::read_to_string(timerfile) {
+            Ok(string) => toml::from_str(&string)
+                .map(Some)
+                .map_err(|err| Error::ConfigInvalid(format!("timer.toml: {}", err))),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// The running timer, failing when there is none
+    fn running() -> Result<Timer> {
+        Timer::load()?.ok_or_else(|| Error::Invalid(String::from("No timer is running")))
+    }
+
+    fn save(&self) -> Result<()> {
+        let simpldir = simpl_path("")?;
+        let toml_string = toml::to_string(self).expect("Could not encode TOML value");
+        if dryrun::enabled() {
+            dryrun::print_diff(&simpldir.join("timer.toml"), &toml_string);
+            return Ok(());
+        }
+        fs::create_dir_all(&simpldir)
+            .and_then(|_| fs::write(simpldir.join("timer.toml"), toml_string))
+            .map_err(|err| Error::Io(format!("Failed to write timer state: {}", err)))
+    }
+
+    fn clear() -> Result<()> {
+        let timerfile = simpl_path("timer.toml")?;
+        if dryrun::enabled() {
+            dryrun::print_diff(&timerfile, "");
+            return Ok(());
+        }
+        fs::remove_file(timerfile)
+            .map_err(|err| Error::Io(format!("Failed to remove timer state: {}", err)))
+    }
+
+    fn is_paused(&self) -> bool {
+        self.resumed_at.is_none()
+    }
+
+    fn pause(&mut self, now: DateTime<Utc>) {
+        if let Some(resumed_at) = self.resumed_at {
+            self.elapsed += (now - resumed_at).num_seconds();
+            self.resumed_at = None;
+        }
+    }
+
+    fn resume(&mut self, now: DateTime<Utc>) {
+        if self.is_paused() {
+            self.resumed_at = Some(now);
+        }
+    }
+
+    fn elapsed_seconds(&self, now: DateTime<Utc>) -> i64 {
+        match self.resumed_at {
+            Some(resumed_at) => self.elapsed + (now - resumed_at).num_seconds(),
+            None => self.elapsed,
+        }
+    }
+
+    /// Elapsed time in hours, rounded to two decimals like `simpl show` displays them
+    pub fn elapsed_hours(&self, now: DateTime<Utc>) -> f64 {
+        round(self.elapsed_seconds(now) as f64 / 3600.0)
+    }
+
+    /// Books the elapsed time, or queues it when the API can't be reached
+    fn book(
+        &self,
+        tickets: &Option<Vec<String>>,
+        context: &Option<String>,
+        overrides: Overrides,
+    ) -> Result<()> {
+        // a timer stopped right away has no hours to book, one left running for days too many
+        let hours = Duration::from_hours(self.elapsed_hours(Utc::now())).map_err(|err| {
+            Error::Invalid(format!(
+                "Can't book the timer: {}, remove {} to discard it",
+                err.to_lowercase(),
+                simpl_path("timer.toml").unwrap_or_default().display()
+            ))
+        })?;
+        let link = Link::from_alias(self.alias.to_owned())?;
+        let start_date = dates::local(self.started_at);
+        let note = format_note(&link, start_date, tickets, context)?;
+        book_hours(&link, hours.hours(), start_date, note, overrides)
+    }
+}
+
+impl std::fmt::Display for Timer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let now = Utc::now();
+        let seconds = self.elapsed_seconds(now);
+        let state = match self.is_paused() {
+            true => "paused".yellow(),
+            false => "running".green(),
+        };
+        write!(
+            f,
+            "{} {} ({})\n{}: {}\n{}: {:02}:{:02}:{:02} ({} hours)",
+            "Timer for".bold().green(),
+            self.alias.bold().green(),
+            state,
+            "started".italic().green(),
+            dates::local(self.started_at).to_string().blue(),
+            "elapsed".italic().green(),
+            seconds / 3600,
+            (seconds % 3600) / 60,
+            seconds % 60,
+            self.elapsed_hours(now).to_string().blue(),
+        )
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "start")]
+pub struct StartCommand {
+    /// Project alias
+    pub project_alias: String,
+}
+
+impl StartCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        if let Some(timer) = Timer::load()? {
+            println!("{}", timer);
+            return Err(Error::Invalid(String::from(
+                "A timer is already running, stop it or use switch",
+            )));
+        }
+        let link = Link::from_alias(self.project_alias.to_owned())?;
+        Timer::new(link.alias.to_owned()).save()?;
+        println!(
+            "Started timer for {} on {}",
+            link.alias.bold().green(),
+            link.description.green()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "stop")]
+pub struct StopCommand {
+    #[structopt(short = "t")]
+    /// Relevant tickets (e.g. LABD-001)
+    pub tickets: Option<Vec<String>>,
+    /// Additional context (e.g. 'took longer due to Amazon issues')
+    #[structopt(short = "m")]
+    pub context: Option<String>,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+}
+
+impl StopCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        Timer::running()?.book(&self.tickets, &self.context, self.checks.overrides())?;
+        Timer::clear()
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "switch")]
+pub struct SwitchCommand {
+    /// Project alias to continue with
+    pub project_alias: String,
+    #[structopt(short = "t")]
+    /// Relevant tickets for the stopped timer (e.g. LABD-001)
+    pub tickets: Option<Vec<String>>,
+    /// Additional context for the stopped timer
+    #[structopt(short = "m")]
+    pub context: Option<String>,
+    #[structopt(flatten)]
+    pub checks: OverrideFlags,
+}
+
+impl SwitchCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_config_env()?;
+        let link = Link::from_alias(self.project_alias.to_owned())?;
+        match Timer::load()? {
+            Some(timer) => {
+                if let Err(err) = timer.book(&self.tickets, &self.context, self.checks.overrides())
+                {
+                    println!("{}", "The running timer is kept".yellow());
+                    return Err(err);
+                }
+                Timer::clear()?;
+            }
+            None => println!("{}", "No timer is running, starting a new one".yellow()),
+        }
+        Timer::new(link.alias.to_owned()).save()?;
+        println!(
+            "Started timer for {} on {}",
+            link.alias.bold().green(),
+            link.description.green()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "status")]
+pub struct StatusCommand {}
+
+impl StatusCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_timezone()?;
+        match Timer::load()? {
+            Some(timer) => println!("{}", timer),
+            None => println!("{}", "No timer is running".yellow()),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "pause")]
+pub struct PauseCommand {}
+
+impl PauseCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_timezone()?;
+        let mut timer = Timer::running()?;
+        timer.pause(Utc::now());
+        timer.save()?;
+        println!("{}", timer);
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "resume")]
+pub struct ResumeCommand {}
+
+impl ResumeCommand {
+    pub fn execute(&self) -> Result<()> {
+        init_timezone()?;
+        let mut timer = Timer::running()?;
+        timer.resume(Utc::now());
+        timer.save()?;
+        println!("{}", timer);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2019, 11, 11).and_hms(hour, minute, 0)
+    }
+
+    fn timer() -> Timer {
+        Timer {
+            alias: String::from("alias"),
+            started_at: at(9, 0),
+            resumed_at: Some(at(9, 0)),
+            elapsed: 0,
+        }
+    }
+
+    #[test]
+    fn counts_time_since_start() {
+        assert_eq!(timer().elapsed_seconds(at(10, 30)), 5400);
+        assert_eq!(timer().elapsed_hours(at(10, 30)), 1.5);
+    }
+
+    #[test]
+    fn excludes_paused_time() {
+        let mut timer = timer();
+        timer.pause(at(10, 0));
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed_seconds(at(12, 0)), 3600);
+        timer.resume(at(12, 0));
+        assert_eq!(timer.elapsed_seconds(at(12, 30)), 5400);
+    }
+
+    #[test]
+    fn pausing_or_resuming_twice_changes_nothing() {
+        let mut timer = timer();
+        timer.pause(at(10, 0));
+        timer.pause(at(11, 0));
+        assert_eq!(timer.elapsed, 3600);
+        timer.resume(at(11, 0));
+        timer.resume(at(11, 30));
+        assert_eq!(timer.resumed_at, Some(at(11, 0)));
+    }
+
+    #[test]
+    fn counts_time_across_a_daylight_saving_change() {
+        let mut timer = timer();
+        timer.started_at = Utc.ymd(2019, 10, 27).and_hms(0, 30, 0);
+        timer.resumed_at = Some(timer.started_at);
+        assert_eq!(
+            timer.elapsed_seconds(Utc.ymd(2019, 10, 27).and_hms(1, 30, 0)),
+            3600
+        );
+    }
+
+    #[test]
+    fn reads_utc_and_older_local_moments() {
+        let timer: Timer = toml::from_str(
+            "alias = \"a\"\nstarted_at = \"2019-11-11T09:00:00Z\"\nresumed_at = \"2019-11-11T09:00:00+01:00\"\nelapsed = 0\n",
+        )
+        .unwrap();
+        assert_eq!(timer.started_at, at(9, 0));
+        assert_eq!(timer.resumed_at, Some(at(8, 0)));
+        let old: std::result::Result<Timer, _> =
+            toml::from_str("alias = \"a\"\nstarted_at = \"2019-11-11T09:00:00\"\nelapsed = 0\n");
+        assert!(old.is_ok());
+        let paused: Timer =
+            toml::from_str("alias = \"a\"\nstarted_at = \"2019-11-11T09:00:00Z\"\nelapsed = 60\n")
+                .unwrap();
+        assert!(paused.is_paused());
+    }
+
+    #[test]
+    fn rounds_hours_to_two_decimals() {
+        let mut timer = timer();
+        timer.elapsed = 1000;
+        timer.resumed_at = None;
+        assert_eq!(timer.elapsed_hours(at(9, 0)), 0.28);
+    }
+}